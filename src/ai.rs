@@ -0,0 +1,411 @@
+// Computer opponent: a negamax search with alpha-beta pruning. Positions are
+// scored from the point of view of the side to move, in hundredths of a man.
+//
+// Searches deepen one turn at a time and can be stopped after any number of
+// nodes, so that a host on a browser's main thread can spread the thinking
+// over several calls and always has the best move of the deepest finished
+// iteration to fall back on.
+use super::bitboard::Bitboard;
+use super::board::{Move, PieceColor};
+use super::game::{GameEngine, GameStatus};
+use super::transposition::{Bound, Entry, TranspositionTable};
+use super::zobrist;
+
+const MAN: i32 = 100;
+const KING: i32 = 160;
+// Per row a man has advanced from its own back row
+const ADVANCE: i32 = 5;
+// Beats any material score; wins found sooner score higher
+const WIN: i32 = 100_000;
+// Scores beyond this are forced wins
+const WON: i32 = WIN - 1000;
+// Deeper than any search finishes in practice
+const MAX_DEPTH: u32 = 64;
+
+/// Size of the transposition table of a search made with `Search::new`.
+pub const DEFAULT_TABLE_BYTES: usize = 1 << 20;
+
+/// Picks the move the side to move should play, searching `depth` turns
+/// ahead (at least one) with `table`, or `None` if the game is over. While a
+/// multi-jump is in progress this is the next jump of the capturing piece.
+pub fn best_move(engine: &GameEngine, depth: u32, table: &mut TranspositionTable) -> Option<Move> {
+    let mut search =
+        Search::with_table(engine, std::mem::replace(table, TranspositionTable::new(0)));
+    search.max_depth = depth.max(1);
+    search.run(u64::MAX);
+    let mv = search.best_move();
+    *table = search.into_table();
+    mv
+}
+
+/// An iterative deepening search of one position that runs in slices of a
+/// given number of nodes.
+pub struct Search {
+    engine: GameEngine,
+    // Of the engine searched from; `engine` moves on as the search plays moves
+    generation: u32,
+    // Moves at the root, the best of the last finished iteration first
+    moves: Vec<Move>,
+    max_depth: u32,
+    depth: u32,
+    // The iteration under way, kept as an explicit stack so that it can be
+    // paused between any two nodes
+    stack: Vec<Frame>,
+    leader: usize,
+    nodes: u64,
+    finished: bool,
+    table: TranspositionTable,
+}
+
+// A position being searched and its moves tried so far.
+struct Frame {
+    key: u64,
+    moves: Vec<Move>,
+    next: usize,
+    depth: u32,
+    // `alpha` as the frame was entered with; scores at or below it are upper bounds
+    floor: i32,
+    alpha: i32,
+    beta: i32,
+    best: Option<Move>,
+}
+
+impl Search {
+    pub fn new(engine: &GameEngine) -> Search {
+        Search::with_table(engine, TranspositionTable::new(DEFAULT_TABLE_BYTES))
+    }
+
+    /// Searches with a table handed over from an earlier search, which saves
+    /// allocating it again and lets this search reuse what it learned.
+    pub fn with_table(engine: &GameEngine, mut table: TranspositionTable) -> Search {
+        table.new_search();
+        let moves = engine.legal_moves();
+        Search {
+            engine: engine.detached(),
+            generation: engine.generation(),
+            finished: moves.is_empty(),
+            moves,
+            max_depth: MAX_DEPTH,
+            depth: 0,
+            stack: Vec::new(),
+            leader: 0,
+            nodes: 0,
+            table,
+        }
+    }
+
+    /// Searches up to `nodes` more positions, picking up exactly where the
+    /// last call stopped. Returns true once more searching cannot change the
+    /// result.
+    pub fn run(&mut self, nodes: u64) -> bool {
+        let limit = self.nodes.saturating_add(nodes);
+        while !self.finished {
+            if self.stack.is_empty() {
+                self.stack.push(Frame {
+                    key: position_key(&self.engine),
+                    moves: self.moves.clone(),
+                    next: 0,
+                    depth: self.depth + 1,
+                    floor: -WIN - 1,
+                    alpha: -WIN - 1,
+                    beta: WIN + 1,
+                    best: None,
+                });
+            }
+            let top = self.stack.last().unwrap();
+            if top.next < top.moves.len() && top.alpha < top.beta {
+                if self.nodes >= limit {
+                    return false;
+                }
+                let (mv, depth, alpha, beta) =
+                    (top.moves[top.next], top.depth, top.alpha, top.beta);
+                self.nodes += 1;
+                self.enter(&mv, depth, alpha, beta);
+            } else {
+                let frame = self.stack.pop().unwrap();
+                if self.stack.is_empty() {
+                    self.finish_iteration(frame.alpha);
+                } else {
+                    self.store(&frame);
+                    self.leave(frame.alpha);
+                }
+            }
+        }
+        true
+    }
+
+    /// The best move found so far: that of the last finished iteration, unless
+    /// the iteration under way has already found a better one.
+    pub fn best_move(&self) -> Option<Move> {
+        self.moves.get(self.leader).copied()
+    }
+
+    /// How many turns ahead the last finished iteration looked.
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Generation of the engine this search was started from, for telling
+    /// whether that engine has changed since.
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    pub fn table(&self) -> &TranspositionTable {
+        &self.table
+    }
+
+    pub fn into_table(self) -> TranspositionTable {
+        self.table
+    }
+
+    // Plays the next move of the top frame, then scores the position it leads
+    // to straight away or pushes a frame to search it. A jump that leaves the
+    // same side to move does not use up a turn of the depth.
+    fn enter(&mut self, mv: &Move, depth: u32, alpha: i32, beta: i32) {
+        let turn = self.engine.current_turn();
+        self.engine.move_piece(mv).unwrap();
+        let (depth, alpha, beta) = if self.engine.current_turn() == turn {
+            (depth, alpha, beta)
+        } else {
+            (depth - 1, -beta, -alpha)
+        };
+        let ply = self.stack.len() as i32;
+        let score = match self.engine.status() {
+            GameStatus::Won(color) if color == self.engine.current_turn() => Some(WIN - ply),
+            GameStatus::Won(_) => Some(-(WIN - ply)),
+            GameStatus::Draw => Some(0),
+            GameStatus::InProgress if depth == 0 => Some(evaluate(&self.engine)),
+            GameStatus::InProgress => None,
+        };
+        if let Some(score) = score {
+            return self.leave(score);
+        }
+        let key = position_key(&self.engine);
+        let entry = self.table.probe(key);
+        if let Some(entry) = entry
+            && entry.depth >= depth
+        {
+            let score = from_table(entry.score, ply);
+            let settled = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => score >= beta,
+                Bound::Upper => score <= alpha,
+            };
+            if settled {
+                return self.leave(score);
+            }
+        }
+        let mut moves = self.engine.legal_moves();
+        // Try the move that was best here before first
+        if let Some(best) = entry.and_then(|e| e.best)
+            && let Some(i) = moves.iter().position(|m| *m == best)
+        {
+            moves[..=i].rotate_right(1);
+        }
+        self.stack.push(Frame {
+            key,
+            moves,
+            next: 0,
+            depth,
+            floor: alpha,
+            alpha,
+            beta,
+            best: None,
+        });
+    }
+
+    // Takes back the move that led to a position worth `score` to its side to
+    // move and hands the score to the frame that played it.
+    fn leave(&mut self, score: i32) {
+        let turn = self.engine.current_turn();
+        self.engine.undo();
+        let score = if self.engine.current_turn() == turn {
+            score
+        } else {
+            -score
+        };
+        let root = self.stack.len() == 1;
+        let frame = self.stack.last_mut().unwrap();
+        if score > frame.alpha {
+            frame.alpha = score;
+            frame.best = Some(frame.moves[frame.next]);
+            if root {
+                self.leader = frame.next;
+            }
+        }
+        frame.next += 1;
+    }
+
+    // Records the outcome of a frame just taken off the stack.
+    fn store(&mut self, frame: &Frame) {
+        let bound = if frame.alpha >= frame.beta {
+            Bound::Lower
+        } else if frame.alpha > frame.floor {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        let entry = Entry {
+            bound,
+            depth: frame.depth,
+            score: to_table(frame.alpha, self.stack.len() as i32),
+            best: frame.best,
+        };
+        self.table.store(frame.key, entry);
+    }
+
+    fn finish_iteration(&mut self, score: i32) {
+        // Search the new best move first in the next iteration
+        let best = self.moves.remove(self.leader);
+        self.moves.insert(0, best);
+        self.leader = 0;
+        self.depth += 1;
+        self.finished = self.depth >= self.max_depth || self.moves.len() == 1 || score.abs() > WON;
+    }
+}
+
+fn position_key(engine: &GameEngine) -> u64 {
+    engine.hash() ^ engine.pending_jump().map_or(0, zobrist::pending_jump)
+}
+
+// Wins are stored as distances from the stored position rather than from the
+// root, so that an entry stays right wherever in the tree it is found again.
+fn to_table(score: i32, ply: i32) -> i32 {
+    match score {
+        s if s > WON => s + ply,
+        s if s < -WON => s - ply,
+        s => s,
+    }
+}
+
+fn from_table(score: i32, ply: i32) -> i32 {
+    match score {
+        s if s > WON => s - ply,
+        s if s < -WON => s + ply,
+        s => s,
+    }
+}
+
+fn evaluate(engine: &GameEngine) -> i32 {
+    let turn = engine.current_turn();
+    let board = engine.bitboard();
+    side_value(&board, turn) - side_value(&board, turn.opponent())
+}
+
+fn side_value(board: &Bitboard, color: PieceColor) -> i32 {
+    let pieces = board.side(color);
+    let men = pieces & !board.kings;
+    // Bits count up in fours from Black's back row
+    let advanced: u32 = (0..8)
+        .map(|row| {
+            let rows = match color {
+                PieceColor::White => 7 - row,
+                PieceColor::Black => row,
+            };
+            (men >> (4 * row) & 0xf).count_ones() * rows
+        })
+        .sum();
+    MAN * men.count_ones() as i32
+        + KING * (pieces & board.kings).count_ones() as i32
+        + ADVANCE * advanced as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{Coordinate, GamePiece};
+
+    #[test]
+    fn running_in_slices_reaches_the_same_result_as_one_run() {
+        let engine = GameEngine::new();
+        let mut whole = Search::new(&engine);
+        whole.max_depth = 5;
+        assert!(whole.run(u64::MAX));
+
+        let mut sliced = Search::new(&engine);
+        sliced.max_depth = 5;
+        let mut slices = 0;
+        while !sliced.run(97) {
+            slices += 1;
+        }
+        assert!(slices > 1);
+        assert_eq!(sliced.best_move(), whole.best_move());
+        assert_eq!(sliced.depth(), whole.depth());
+        assert_eq!(sliced.nodes(), whole.nodes());
+    }
+
+    #[test]
+    fn generation_is_that_of_the_engine_searched() {
+        let mut engine = GameEngine::new();
+        let mv = engine.legal_moves()[0];
+        engine.move_piece(&mv).unwrap();
+        let mut search = Search::new(&engine);
+        search.run(500);
+        assert_eq!(search.generation(), engine.generation());
+    }
+
+    #[test]
+    fn best_move_finds_a_forced_win() {
+        // Black's king shuts in White's last man by moving next to it
+        let engine = GameEngine::from_position(
+            PieceColor::Black,
+            &[
+                (Coordinate(7, 6), GamePiece::new(PieceColor::White)),
+                (
+                    Coordinate(5, 6),
+                    GamePiece {
+                        color: PieceColor::Black,
+                        crowned: true,
+                    },
+                ),
+            ],
+        );
+        let win = Move::new((5, 6), (6, 7));
+        assert!(engine.legal_moves().len() > 1);
+        assert_eq!(
+            best_move(
+                &engine,
+                3,
+                &mut TranspositionTable::new(DEFAULT_TABLE_BYTES)
+            ),
+            Some(win)
+        );
+
+        let mut after = engine.detached();
+        after.move_piece(&win).unwrap();
+        assert_eq!(after.status(), GameStatus::Won(PieceColor::Black));
+    }
+
+    #[test]
+    fn best_move_hands_the_table_back() {
+        let mut table = TranspositionTable::new(4096);
+        assert!(best_move(&GameEngine::new(), 3, &mut table).is_some());
+        assert_eq!(table.size_bytes(), 4096);
+        assert!(table.stats().stores > 0);
+    }
+
+    #[test]
+    fn best_move_is_none_once_the_game_is_over() {
+        let engine = GameEngine::from_position(
+            PieceColor::White,
+            &[(Coordinate(0, 5), GamePiece::new(PieceColor::Black))],
+        );
+        assert_eq!(
+            best_move(
+                &engine,
+                3,
+                &mut TranspositionTable::new(DEFAULT_TABLE_BYTES)
+            ),
+            None
+        );
+    }
+}
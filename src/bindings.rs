@@ -0,0 +1,158 @@
+// JavaScript-friendly API generated by wasm-bindgen. Values come back as plain
+// JS objects and arrays, and rejected moves are thrown as `Error`s named
+// `MoveError` whose `code` matches the C ABI error codes.
+use js_sys::{Array, Object, Reflect};
+use wasm_bindgen::prelude::*;
+
+use super::board::{Coordinate, GamePiece, Move, PieceColor};
+use super::game::{GameEngine, GameStatus, MoveError, MoveResult};
+
+#[wasm_bindgen]
+pub struct Game {
+    engine: GameEngine,
+}
+
+#[wasm_bindgen]
+impl Game {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Game {
+        Game {
+            engine: GameEngine::new(),
+        }
+    }
+
+    /// Every piece on the board as `{ x, y, color, crowned }`.
+    pub fn pieces(&self) -> Array {
+        self.engine
+            .pieces()
+            .into_iter()
+            .map(|(at, piece)| piece_at(at, piece))
+            .collect()
+    }
+
+    #[wasm_bindgen(js_name = currentTurn)]
+    pub fn current_turn(&self) -> String {
+        color_name(self.engine.current_turn()).to_string()
+    }
+
+    /// `{ state: "inProgress" | "won" | "draw", winner? }`
+    pub fn status(&self) -> JsValue {
+        match self.engine.status() {
+            GameStatus::InProgress => object(&[("state", "inProgress".into())]),
+            GameStatus::Won(color) => object(&[
+                ("state", "won".into()),
+                ("winner", color_name(color).into()),
+            ]),
+            GameStatus::Draw => object(&[("state", "draw".into())]),
+        }
+    }
+
+    /// Legal moves as `{ from: { x, y }, to: { x, y }, capture }`.
+    #[wasm_bindgen(js_name = legalMoves)]
+    pub fn legal_moves(&self) -> Array {
+        self.engine.legal_moves().iter().map(move_object).collect()
+    }
+
+    #[wasm_bindgen(js_name = legalMovesFrom)]
+    pub fn legal_moves_from(&self, x: u32, y: u32) -> Array {
+        self.engine
+            .legal_moves_from(Coordinate(x as usize, y as usize))
+            .iter()
+            .map(move_object)
+            .collect()
+    }
+
+    /// Plays a move and returns `{ from, to, capture, crowned, turnContinues,
+    /// captured }`, or throws a `MoveError`.
+    #[wasm_bindgen(js_name = movePiece)]
+    pub fn move_piece(&mut self, fx: u32, fy: u32, tx: u32, ty: u32) -> Result<JsValue, JsValue> {
+        let mv = Move::new((fx as usize, fy as usize), (tx as usize, ty as usize));
+        match self.engine.move_piece(&mv) {
+            Ok(mr) => Ok(result_object(&mr)),
+            Err(e) => Err(move_error(e)),
+        }
+    }
+
+    /// Takes back the last move and returns it, or `null` if there was none.
+    pub fn undo(&mut self) -> JsValue {
+        self.engine
+            .undo()
+            .map_or(JsValue::NULL, |mr| result_object(&mr))
+    }
+
+    /// Replays the last undone move and returns it, or `null` if there was none.
+    pub fn redo(&mut self) -> JsValue {
+        self.engine
+            .redo()
+            .map_or(JsValue::NULL, |mr| result_object(&mr))
+    }
+
+    pub fn reset(&mut self) {
+        self.engine.reset();
+    }
+}
+
+impl Default for Game {
+    fn default() -> Game {
+        Game::new()
+    }
+}
+
+fn object(entries: &[(&str, JsValue)]) -> JsValue {
+    let obj = Object::new();
+    for (key, value) in entries {
+        Reflect::set(&obj, &JsValue::from_str(key), value).unwrap();
+    }
+    obj.into()
+}
+
+fn point(Coordinate(x, y): Coordinate) -> JsValue {
+    object(&[("x", (x as u32).into()), ("y", (y as u32).into())])
+}
+
+fn move_object(mv: &Move) -> JsValue {
+    object(&[
+        ("from", point(mv.from)),
+        ("to", point(mv.to)),
+        ("capture", mv.is_jump().into()),
+    ])
+}
+
+fn result_object(mr: &MoveResult) -> JsValue {
+    let captured = match mr.captured {
+        Some((at, piece)) => piece_at(at, piece),
+        None => JsValue::NULL,
+    };
+    let obj = move_object(&mr.mv);
+    for (key, value) in [
+        ("crowned", mr.crowned.into()),
+        ("turnContinues", mr.turn_continues.into()),
+        ("captured", captured),
+    ] {
+        Reflect::set(&obj, &JsValue::from_str(key), &value).unwrap();
+    }
+    obj
+}
+
+fn piece_at(Coordinate(x, y): Coordinate, piece: GamePiece) -> JsValue {
+    object(&[
+        ("x", (x as u32).into()),
+        ("y", (y as u32).into()),
+        ("color", color_name(piece.color).into()),
+        ("crowned", piece.crowned.into()),
+    ])
+}
+
+fn move_error(err: MoveError) -> JsValue {
+    let error = js_sys::Error::new(&err.to_string());
+    error.set_name("MoveError");
+    Reflect::set(&error, &"code".into(), &crate::error_code(err).into()).unwrap();
+    error.into()
+}
+
+fn color_name(color: PieceColor) -> &'static str {
+    match color {
+        PieceColor::White => "white",
+        PieceColor::Black => "black",
+    }
+}
@@ -0,0 +1,415 @@
+// The board as three 32-bit sets of the dark squares, bit `n - 1` standing for
+// square `n` (numbered as in `Coordinate::from_square`). Rows alternate in how
+// their squares line up with the next row, so a diagonal step is a shift by 3,
+// 4 or 5 depending on the row, with masks keeping pieces from wrapping around
+// the board's edges.
+use super::board::{Coordinate, GamePiece, Move, PieceColor};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bitboard {
+    pub white: u32,
+    pub black: u32,
+    pub kings: u32,
+}
+
+// Rows whose dark squares have even x (odd y, Black's back row among them)
+// and those whose dark squares have odd x.
+const EVEN_X_ROWS: u32 = 0x0f0f_0f0f;
+const ODD_X_ROWS: u32 = 0xf0f0_f0f0;
+// Squares with diagonal neighbours toward x = 0
+const NOT_LEFT_EDGE: u32 = 0x0707_0707 | ODD_X_ROWS;
+// Squares with diagonal neighbours toward x = 7
+const NOT_RIGHT_EDGE: u32 = 0xe0e0_e0e0 | EVEN_X_ROWS;
+
+// Diagonal steps as (dx, dy), in the order the engine has always listed moves.
+const JUMP_STEPS: [(i32, i32); 4] = [(1, -1), (1, 1), (-1, -1), (-1, 1)];
+const MOVE_STEPS: [(i32, i32); 4] = [(-1, 1), (1, 1), (1, -1), (-1, -1)];
+
+// Square bits in order of x, then y, the order moves are listed in.
+const BY_COLUMN: [u32; 32] = {
+    let mut bits = [0; 32];
+    let mut i = 0;
+    let mut x = 0;
+    while x < 8 {
+        let mut y = (x + 1) % 2;
+        while y < 8 {
+            bits[i] = ((7 - y) * 4 + (7 - x) / 2) as u32;
+            i += 1;
+            y += 2;
+        }
+        x += 1;
+    }
+    bits
+};
+
+impl Bitboard {
+    pub fn from_pieces(pieces: &[(Coordinate, GamePiece)]) -> Bitboard {
+        let mut board = Bitboard::default();
+        for (coord, piece) in pieces {
+            board.set(*coord, Some(*piece));
+        }
+        board
+    }
+
+    pub fn get(&self, coord: Coordinate) -> Option<GamePiece> {
+        let bit = bit(coord)?;
+        let color = if self.white & bit != 0 {
+            PieceColor::White
+        } else if self.black & bit != 0 {
+            PieceColor::Black
+        } else {
+            return None;
+        };
+        let crowned = self.kings & bit != 0;
+        Some(GamePiece { color, crowned })
+    }
+
+    // Light squares can hold no piece, so setting one does nothing.
+    pub fn set(&mut self, coord: Coordinate, piece: Option<GamePiece>) {
+        let Some(bit) = bit(coord) else {
+            return;
+        };
+        self.white &= !bit;
+        self.black &= !bit;
+        self.kings &= !bit;
+        if let Some(piece) = piece {
+            match piece.color {
+                PieceColor::White => self.white |= bit,
+                PieceColor::Black => self.black |= bit,
+            }
+            if piece.crowned {
+                self.kings |= bit;
+            }
+        }
+    }
+
+    /// Every piece, in order of x and then y.
+    pub fn pieces(&self) -> Vec<(Coordinate, GamePiece)> {
+        BY_COLUMN
+            .iter()
+            .filter_map(|&n| {
+                let coord = coordinate(n);
+                self.get(coord).map(|piece| (coord, piece))
+            })
+            .collect()
+    }
+
+    pub fn side(&self, color: PieceColor) -> u32 {
+        match color {
+            PieceColor::White => self.white,
+            PieceColor::Black => self.black,
+        }
+    }
+
+    pub fn empty(&self) -> u32 {
+        !(self.white | self.black)
+    }
+
+    /// The moves `color` may make, captures being mandatory: its jumps if it
+    /// has any, otherwise its plain moves.
+    pub fn moves(&self, color: PieceColor) -> Vec<Move> {
+        let jumps = self.jumps(color);
+        if !jumps.is_empty() {
+            return jumps;
+        }
+        let mut moves = Vec::new();
+        for n in self.by_column(color) {
+            for step in self.steps(n, &MOVE_STEPS) {
+                let to = shift(1 << n, step) & self.empty();
+                if to != 0 {
+                    moves.push(Move {
+                        from: coordinate(n),
+                        to: coordinate(to.trailing_zeros()),
+                    });
+                }
+            }
+        }
+        moves
+    }
+
+    pub fn jumps(&self, color: PieceColor) -> Vec<Move> {
+        self.by_column(color)
+            .flat_map(|n| self.jumps_from_bit(n))
+            .collect()
+    }
+
+    /// The jumps open to the piece on `coord`, if there is one.
+    pub fn jumps_from(&self, coord: Coordinate) -> Vec<Move> {
+        match bit(coord) {
+            Some(bit) if bit & !self.empty() != 0 => self.jumps_from_bit(bit.trailing_zeros()),
+            _ => Vec::new(),
+        }
+    }
+
+    fn jumps_from_bit(&self, n: u32) -> Vec<Move> {
+        let from = 1 << n;
+        let opponents = if self.white & from != 0 {
+            self.black
+        } else {
+            self.white
+        };
+        let mut jumps = Vec::new();
+        for step in self.steps(n, &JUMP_STEPS) {
+            let over = shift(from, step) & opponents;
+            let to = shift(over, step) & self.empty();
+            if to != 0 {
+                jumps.push(Move {
+                    from: coordinate(n),
+                    to: coordinate(to.trailing_zeros()),
+                });
+            }
+        }
+        jumps
+    }
+
+    // Squares of `color`'s pieces, in order of x and then y.
+    fn by_column(&self, color: PieceColor) -> impl Iterator<Item = u32> {
+        let side = self.side(color);
+        BY_COLUMN.into_iter().filter(move |n| side & 1 << n != 0)
+    }
+
+    // The directions the piece on bit `n` may go in: men only advance (White
+    // toward y = 7, Black toward y = 0), kings go either way.
+    fn steps<'a>(&self, n: u32, steps: &'a [(i32, i32)]) -> impl Iterator<Item = (i32, i32)> + 'a {
+        let forward = if self.kings & 1 << n != 0 {
+            0
+        } else if self.white & 1 << n != 0 {
+            1
+        } else {
+            -1
+        };
+        steps
+            .iter()
+            .copied()
+            .filter(move |&(_, dy)| forward == 0 || dy == forward)
+    }
+}
+
+// One diagonal step of every square in `bits`. Squares number upward from
+// Black's back row (y = 7), so a step toward y = 0 is a left shift.
+fn shift(bits: u32, (dx, dy): (i32, i32)) -> u32 {
+    let (even, odd) = (bits & EVEN_X_ROWS, bits & ODD_X_ROWS);
+    match (dx, dy) {
+        (1, -1) => (even << 4) | ((odd & NOT_RIGHT_EDGE) << 3),
+        (-1, -1) => ((even & NOT_LEFT_EDGE) << 5) | (odd << 4),
+        (1, 1) => (even >> 4) | ((odd & NOT_RIGHT_EDGE) >> 5),
+        (-1, 1) => ((even & NOT_LEFT_EDGE) >> 3) | (odd >> 4),
+        _ => unreachable!(),
+    }
+}
+
+fn bit(coord: Coordinate) -> Option<u32> {
+    coord.to_square().map(|n| 1 << (n - 1))
+}
+
+fn coordinate(n: u32) -> Coordinate {
+    Coordinate::from_square(n + 1).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::random;
+
+    // The board as a plain array indexed by x and y, with moves found from the
+    // diagonal neighbours `Coordinate` lists rather than the shift tables.
+    type Squares = [[Option<GamePiece>; 8]; 8];
+
+    fn squares(board: &Bitboard) -> Squares {
+        let mut squares = [[None; 8]; 8];
+        for (Coordinate(x, y), piece) in board.pieces() {
+            squares[x][y] = Some(piece);
+        }
+        squares
+    }
+
+    fn piece_at(squares: &Squares, Coordinate(x, y): Coordinate) -> Option<GamePiece> {
+        squares[x][y]
+    }
+
+    // Men only head for the opposing back row, kings go either way.
+    fn allowed(piece: GamePiece, from: Coordinate, to: Coordinate) -> bool {
+        piece.crowned
+            || match piece.color {
+                PieceColor::White => to.1 > from.1,
+                PieceColor::Black => to.1 < from.1,
+            }
+    }
+
+    fn reference_jumps_from(squares: &Squares, from: Coordinate) -> Vec<Move> {
+        let Some(piece) = piece_at(squares, from) else {
+            return Vec::new();
+        };
+        from.jump_targets_from()
+            .filter(|&to| to.on_board() && allowed(piece, from, to))
+            .filter(|&to| {
+                let over = Coordinate((from.0 + to.0) / 2, (from.1 + to.1) / 2);
+                matches!(piece_at(squares, over), Some(p) if p.color != piece.color)
+                    && piece_at(squares, to).is_none()
+            })
+            .map(|to| Move { from, to })
+            .collect()
+    }
+
+    fn reference_moves(squares: &Squares, color: PieceColor) -> Vec<Move> {
+        let own: Vec<Coordinate> = (0..8)
+            .flat_map(|x| (0..8).map(move |y| Coordinate(x, y)))
+            .filter(|&c| matches!(piece_at(squares, c), Some(p) if p.color == color))
+            .collect();
+        let jumps: Vec<Move> = own
+            .iter()
+            .flat_map(|&from| reference_jumps_from(squares, from))
+            .collect();
+        if !jumps.is_empty() {
+            return jumps;
+        }
+        let mut moves = Vec::new();
+        for &from in &own {
+            let piece = piece_at(squares, from).unwrap();
+            for to in from.move_targets_from() {
+                if to.on_board() && allowed(piece, from, to) && piece_at(squares, to).is_none() {
+                    moves.push(Move { from, to });
+                }
+            }
+        }
+        moves
+    }
+
+    // The moves in a fixed order, so that lists are compared as sets.
+    fn sorted(mut moves: Vec<Move>) -> Vec<Move> {
+        moves.sort_by_key(|m| (m.from.0, m.from.1, m.to.0, m.to.1));
+        moves
+    }
+
+    fn dark_squares() -> Vec<Coordinate> {
+        (1..=32).filter_map(Coordinate::from_square).collect()
+    }
+
+    fn assert_matches_reference(board: &Bitboard) {
+        let squares = squares(board);
+        for color in [PieceColor::White, PieceColor::Black] {
+            assert_eq!(
+                sorted(board.moves(color)),
+                sorted(reference_moves(&squares, color)),
+                "{:?}",
+                board
+            );
+        }
+        for x in 0..8 {
+            for y in 0..8 {
+                let coord = Coordinate(x, y);
+                assert_eq!(
+                    sorted(board.jumps_from(coord)),
+                    sorted(reference_jumps_from(&squares, coord)),
+                    "{:?} from {:?}",
+                    board,
+                    coord
+                );
+            }
+        }
+    }
+
+    // Plays `mv` on the board, returning whether the same piece must jump again.
+    fn play(board: &mut Bitboard, mv: Move) -> bool {
+        let piece = board.get(mv.from).unwrap();
+        board.set(mv.from, None);
+        let jump = mv.from.0.abs_diff(mv.to.0) == 2;
+        if jump {
+            let over = Coordinate((mv.from.0 + mv.to.0) / 2, (mv.from.1 + mv.to.1) / 2);
+            board.set(over, None);
+        }
+        let last_row = match piece.color {
+            PieceColor::White => 7,
+            PieceColor::Black => 0,
+        };
+        let crowned = !piece.crowned && mv.to.1 == last_row;
+        board.set(
+            mv.to,
+            Some(GamePiece {
+                crowned: piece.crowned || crowned,
+                ..piece
+            }),
+        );
+        jump && !crowned && !board.jumps_from(mv.to).is_empty()
+    }
+
+    #[test]
+    fn moves_match_the_reference_in_random_playouts() {
+        let mut seed = 0x6c8e_9cf5_7087_4b31;
+        let (mut kings, mut multi_jumps) = (0, 0);
+        for _ in 0..200 {
+            let mut board = Bitboard::from_pieces(&crate::game::GameEngine::new().pieces());
+            let mut turn = PieceColor::Black;
+            let mut pending = None;
+            for _ in 0..300 {
+                assert_matches_reference(&board);
+                let moves = match pending {
+                    Some(coord) => {
+                        multi_jumps += 1;
+                        let jumps = board.jumps_from(coord);
+                        let reference = reference_jumps_from(&squares(&board), coord);
+                        assert_eq!(sorted(jumps.clone()), sorted(reference));
+                        jumps
+                    }
+                    None => board.moves(turn),
+                };
+                if moves.is_empty() {
+                    break;
+                }
+                let mv = moves[random(&mut seed) % moves.len()];
+                if play(&mut board, mv) {
+                    pending = Some(mv.to);
+                } else {
+                    pending = None;
+                    turn = turn.opponent();
+                }
+                kings += board.kings.count_ones();
+            }
+        }
+        assert!(kings > 0 && multi_jumps > 0);
+    }
+
+    #[test]
+    fn moves_match_the_reference_in_random_positions() {
+        let mut seed = 0x1d87_2b41_c7e3_905f;
+        for _ in 0..2000 {
+            let mut board = Bitboard::default();
+            for coord in dark_squares() {
+                let piece = |color, crowned| Some(GamePiece { color, crowned });
+                let square = match random(&mut seed) % 8 {
+                    0 => piece(PieceColor::White, false),
+                    1 => piece(PieceColor::Black, false),
+                    2 => piece(PieceColor::White, true),
+                    3 => piece(PieceColor::Black, true),
+                    _ => None,
+                };
+                board.set(coord, square);
+            }
+            assert_matches_reference(&board);
+        }
+    }
+
+    #[test]
+    fn pieces_on_edge_squares_do_not_wrap_around() {
+        let edges = dark_squares()
+            .into_iter()
+            .filter(|&Coordinate(x, y)| x == 0 || x == 7 || y == 0 || y == 7);
+        for coord in edges {
+            for color in [PieceColor::White, PieceColor::Black] {
+                for crowned in [false, true] {
+                    let mut board = Bitboard::default();
+                    board.set(coord, Some(GamePiece { color, crowned }));
+                    assert_matches_reference(&board);
+
+                    // Surrounded by opponents, so any jump that stays on the
+                    // board is open
+                    for next in coord.move_targets_from().filter(|c| c.on_board()) {
+                        board.set(next, Some(GamePiece::new(color.opponent())));
+                    }
+                    assert_matches_reference(&board);
+                }
+            }
+        }
+    }
+}
@@ -1,9 +1,21 @@
+use std::fmt;
+
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PieceColor {
     White,
     Black,
 }
+impl PieceColor {
+    pub fn opponent(self) -> PieceColor {
+        match self {
+            PieceColor::White => PieceColor::Black,
+            PieceColor::Black => PieceColor::White,
+        }
+    }
+}
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GamePiece {
     pub color: PieceColor,
     pub crowned: bool,
@@ -24,12 +36,30 @@ impl GamePiece {
 }
 
 #[derive(Debug, Clone, PartialEq, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Coordinate(pub usize, pub usize);
 impl Coordinate {
     pub fn on_board(self) -> bool {
         let Coordinate(x, y) = self;
         x <= 7 && y <= 7
     }
+    // Squares are numbered 1-32 across the dark squares, starting from Black's back row.
+    pub fn from_square(square: u32) -> Option<Coordinate> {
+        if !(1..=32).contains(&square) {
+            return None;
+        }
+        let (row, col) = ((square - 1) / 4, (square - 1) % 4);
+        let y = 7 - row as usize;
+        let x = 7 - 2 * col as usize - y % 2;
+        Some(Coordinate(x, y))
+    }
+    pub fn to_square(self) -> Option<u32> {
+        let Coordinate(x, y) = self;
+        if !self.on_board() || (x + y) % 2 == 0 {
+            return None;
+        }
+        Some((7 - y as u32) * 4 + (7 - x as u32) / 2 + 1)
+    }
     pub fn jump_targets_from(&self) -> impl Iterator<Item = Coordinate> {
         let mut jumps = Vec::new();
         let Coordinate(x, y) = *self;
@@ -62,7 +92,21 @@ impl Coordinate {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BoardError {
+    OffBoard(Coordinate),
+}
+impl fmt::Display for BoardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BoardError::OffBoard(Coordinate(x, y)) => write!(f, "({}, {}) is off the board", x, y),
+        }
+    }
+}
+impl std::error::Error for BoardError {}
+
 #[derive(Debug, Clone, PartialEq, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Move {
     pub from: Coordinate,
     pub to: Coordinate,
@@ -74,4 +118,7 @@ impl Move {
             to: Coordinate(to.0, to.1),
         }
     }
+    pub fn is_jump(&self) -> bool {
+        self.from.0.abs_diff(self.to.0) == 2 && self.from.1.abs_diff(self.to.1) == 2
+    }
 }
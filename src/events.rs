@@ -0,0 +1,149 @@
+// Game events for the host, delivered by a `GameObserver` attached to every
+// engine the exports hand out. By default each event calls one of the
+// imported `notify_*` functions; with the `event-queue` feature events are
+// queued instead and the host drains them with the `poll_event` export.
+use super::board::{Coordinate, GamePiece, Move, PieceColor};
+use super::game::{GameStatus, MoveResult};
+use super::observer::GameObserver;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    PieceMoved { from: (i32, i32), to: (i32, i32) },
+    PieceCrowned(i32, i32),
+    PieceRemoved(i32, i32),
+    PiecePlaced(i32, i32, i32),
+    TurnChanged(i32),
+    GameOver(i32),
+    GameReset,
+    GameResumed,
+}
+
+#[cfg(feature = "event-queue")]
+impl Event {
+    // Kind code and arguments as reported by `poll_event`.
+    pub fn encode(&self) -> (i32, [i32; 4]) {
+        match *self {
+            Event::PieceMoved { from, to } => (1, [from.0, from.1, to.0, to.1]),
+            Event::PieceCrowned(x, y) => (2, [x, y, 0, 0]),
+            Event::PieceRemoved(x, y) => (3, [x, y, 0, 0]),
+            Event::PiecePlaced(x, y, piece) => (4, [x, y, piece, 0]),
+            Event::TurnChanged(color) => (5, [color, 0, 0, 0]),
+            Event::GameOver(status) => (6, [status, 0, 0, 0]),
+            Event::GameReset => (7, [0; 4]),
+            Event::GameResumed => (8, [0; 4]),
+        }
+    }
+}
+
+// Forwards one game's changes to the host; `handle` is 0 for the default game.
+pub struct HostObserver {
+    #[cfg_attr(not(feature = "event-queue"), allow(dead_code))]
+    handle: i32,
+}
+
+impl HostObserver {
+    pub fn new(handle: i32) -> HostObserver {
+        HostObserver { handle }
+    }
+
+    #[cfg(not(feature = "event-queue"))]
+    fn emit(&self, event: Event) {
+        unsafe {
+            match event {
+                Event::PieceMoved { from, to } => notify_piecemoved(from.0, from.1, to.0, to.1),
+                Event::PieceCrowned(x, y) => notify_piececrowned(x, y),
+                Event::PieceRemoved(x, y) => notify_pieceremoved(x, y),
+                Event::PiecePlaced(x, y, piece) => notify_pieceplaced(x, y, piece),
+                // Hosts on callbacks follow the turn through `get_current_turn`
+                Event::TurnChanged(_) => {}
+                Event::GameOver(status) => notify_gameover(status),
+                Event::GameReset => notify_gamereset(),
+                Event::GameResumed => notify_gameresumed(),
+            }
+        }
+    }
+
+    #[cfg(feature = "event-queue")]
+    fn emit(&self, event: Event) {
+        QUEUE.write().unwrap().push_back((self.handle, event));
+    }
+}
+
+impl GameObserver for HostObserver {
+    fn piece_moved(&mut self, mv: &Move) {
+        self.emit(Event::PieceMoved {
+            from: xy(mv.from),
+            to: xy(mv.to),
+        });
+    }
+
+    fn piece_captured(&mut self, at: Coordinate, _piece: GamePiece) {
+        let (x, y) = xy(at);
+        self.emit(Event::PieceRemoved(x, y));
+    }
+
+    fn piece_crowned(&mut self, at: Coordinate) {
+        let (x, y) = xy(at);
+        self.emit(Event::PieceCrowned(x, y));
+    }
+
+    fn turn_changed(&mut self, turn: PieceColor) {
+        self.emit(Event::TurnChanged(GamePiece::new(turn).into()));
+    }
+
+    fn game_over(&mut self, status: GameStatus) {
+        self.emit(Event::GameOver(crate::status_code(status)));
+    }
+
+    fn move_undone(&mut self, result: &MoveResult, piece: GamePiece) {
+        let Move { from, to } = result.mv;
+        self.emit(Event::PieceMoved {
+            from: xy(to),
+            to: xy(from),
+        });
+        // Put back the uncrowned piece and any piece the move had captured
+        if result.crowned {
+            let (x, y) = xy(from);
+            self.emit(Event::PiecePlaced(x, y, piece.into()));
+        }
+        if let Some((at, captured)) = result.captured {
+            let (x, y) = xy(at);
+            self.emit(Event::PiecePlaced(x, y, captured.into()));
+        }
+    }
+
+    fn game_reset(&mut self) {
+        self.emit(Event::GameReset);
+    }
+
+    fn game_resumed(&mut self) {
+        self.emit(Event::GameResumed);
+    }
+}
+
+fn xy(coord: Coordinate) -> (i32, i32) {
+    (coord.0 as i32, coord.1 as i32)
+}
+
+#[cfg(not(feature = "event-queue"))]
+unsafe extern "C" {
+    fn notify_piecemoved(fromX: i32, fromY: i32, toX: i32, toY: i32);
+    fn notify_piececrowned(x: i32, y: i32);
+    fn notify_gameover(status: i32);
+    fn notify_pieceplaced(x: i32, y: i32, piece: i32);
+    fn notify_pieceremoved(x: i32, y: i32);
+    fn notify_gamereset();
+    fn notify_gameresumed();
+}
+
+#[cfg(feature = "event-queue")]
+lazy_static! {
+    static ref QUEUE: mut_static::MutStatic<std::collections::VecDeque<(i32, Event)>> =
+        mut_static::MutStatic::from(std::collections::VecDeque::new());
+}
+
+// Takes the oldest queued event along with the handle of the game it came from.
+#[cfg(feature = "event-queue")]
+pub fn poll() -> Option<(i32, Event)> {
+    QUEUE.write().unwrap().pop_front()
+}
@@ -0,0 +1,105 @@
+use std::fmt;
+
+use super::board::{Coordinate, GamePiece, PieceColor};
+use super::game::GameEngine;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FenError {
+    InvalidTurn(String),
+    InvalidField(String),
+    InvalidSquare(String),
+    DuplicateSquare(u32),
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FenError::InvalidTurn(t) => write!(f, "`{}` is not a side to move", t),
+            FenError::InvalidField(t) => write!(f, "`{}` is not a piece list", t),
+            FenError::InvalidSquare(t) => write!(f, "`{}` is not a square number", t),
+            FenError::DuplicateSquare(n) => write!(f, "square {} is listed twice", n),
+        }
+    }
+}
+impl std::error::Error for FenError {}
+
+/// Parses a position such as `W:W21,22,K30:B1,2` (side to move, then each
+/// side's squares, `K` marking kings and `a-b` covering a range).
+pub fn parse(fen: &str) -> Result<GameEngine, FenError> {
+    let fen = fen.trim().trim_end_matches('.');
+    let mut fields = fen.split(':');
+    let turn = fields.next().unwrap_or("").trim();
+    let current_turn = color(turn).ok_or_else(|| FenError::InvalidTurn(turn.to_string()))?;
+
+    let mut pieces: Vec<(Coordinate, GamePiece)> = Vec::new();
+    for field in fields {
+        let field = field.trim();
+        let invalid = || FenError::InvalidField(field.to_string());
+        let side = field.get(..1).and_then(color).ok_or_else(invalid)?;
+        for entry in field[1..].split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (crowned, squares) = match entry.strip_prefix('K') {
+                Some(rest) => (true, rest),
+                None => (false, entry),
+            };
+            for square in square_range(squares)? {
+                let coord = Coordinate::from_square(square)
+                    .ok_or_else(|| FenError::InvalidSquare(square.to_string()))?;
+                if pieces.iter().any(|(c, _)| *c == coord) {
+                    return Err(FenError::DuplicateSquare(square));
+                }
+                pieces.push((coord, GamePiece { color: side, crowned }));
+            }
+        }
+    }
+    Ok(GameEngine::from_position(current_turn, &pieces))
+}
+
+/// Writes the engine's current position in the format accepted by `parse`.
+pub fn export(engine: &GameEngine) -> String {
+    let mut out = letter(engine.current_turn()).to_string();
+    for side in [PieceColor::White, PieceColor::Black] {
+        let mut squares: Vec<(u32, bool)> = engine
+            .pieces()
+            .into_iter()
+            .filter(|(_, p)| p.color == side)
+            .filter_map(|(c, p)| c.to_square().map(|n| (n, p.crowned)))
+            .collect();
+        squares.sort();
+        let list: Vec<String> = squares
+            .iter()
+            .map(|(n, crowned)| format!("{}{}", if *crowned { "K" } else { "" }, n))
+            .collect();
+        out.push_str(&format!(":{}{}", letter(side), list.join(",")));
+    }
+    out
+}
+
+fn square_range(squares: &str) -> Result<std::ops::RangeInclusive<u32>, FenError> {
+    let number = |s: &str| {
+        s.trim()
+            .parse::<u32>()
+            .map_err(|_| FenError::InvalidSquare(squares.to_string()))
+    };
+    match squares.split_once('-') {
+        Some((first, last)) => Ok(number(first)?..=number(last)?),
+        None => {
+            let n = number(squares)?;
+            Ok(n..=n)
+        }
+    }
+}
+
+fn color(letter: &str) -> Option<PieceColor> {
+    match letter {
+        "W" => Some(PieceColor::White),
+        "B" => Some(PieceColor::Black),
+        _ => None,
+    }
+}
+
+fn letter(color: PieceColor) -> char {
+    match color {
+        PieceColor::White => 'W',
+        PieceColor::Black => 'B',
+    }
+}
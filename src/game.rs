@@ -1,24 +1,189 @@
-use super::board::{Coordinate, GamePiece, Move, PieceColor};
+use std::fmt;
 
+use super::bitboard::Bitboard;
+use super::board::{BoardError, Coordinate, GamePiece, Move, PieceColor};
+use super::observer::GameObserver;
+use super::zobrist;
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GameEngine {
-    board: [[Option<GamePiece>; 8]; 8],
+    board: Bitboard,
+    current_turn: PieceColor,
+    move_count: u32,
+    pending_jump: Option<Coordinate>,
+    status: GameStatus,
+    history: Vec<HistoryEntry>,
+    undone: Vec<Move>,
+    positions: Vec<Position>,
+    quiet_moves: u32,
+    draw_move_limit: u32,
+    generation: u32,
+    hash: u64,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    observers: Vec<Box<dyn GameObserver>>,
+}
+
+/// Default for `GameEngine::set_draw_move_limit`: forty moves by each player.
+pub const DEFAULT_DRAW_MOVE_LIMIT: u32 = 80;
+
+// The parts of the engine state that decide whether a position has repeated.
+type Position = (Bitboard, PieceColor, Option<Coordinate>);
+
+// Everything needed to take a move back and restore the engine exactly.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct HistoryEntry {
+    result: MoveResult,
+    piece: GamePiece,
     current_turn: PieceColor,
     move_count: u32,
+    pending_jump: Option<Coordinate>,
+    status: GameStatus,
+    quiet_moves: u32,
+    hash: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GameStatus {
+    InProgress,
+    Won(PieceColor),
+    Draw,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MoveResult {
     pub mv: Move,
     pub crowned: bool,
+    pub turn_continues: bool,
+    pub captured: Option<(Coordinate, GamePiece)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MoveError {
+    GameOver,
+    OffBoard(Coordinate),
+    NoPieceAtSource,
+    WrongTurn,
+    MustContinueJump,
+    NotDiagonal,
+    TooFar,
+    DestinationOccupied,
+    WrongDirection,
+    NothingToCapture,
+    CaptureRequired,
+}
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveError::GameOver => write!(f, "the game is already over"),
+            MoveError::OffBoard(Coordinate(x, y)) => write!(f, "({}, {}) is off the board", x, y),
+            MoveError::NoPieceAtSource => write!(f, "there is no piece on the source square"),
+            MoveError::WrongTurn => write!(f, "it is not that piece's turn"),
+            MoveError::MustContinueJump => write!(f, "the jumping piece must continue its capture"),
+            MoveError::NotDiagonal => write!(f, "pieces may only move diagonally"),
+            MoveError::TooFar => write!(f, "pieces move one square or jump two"),
+            MoveError::DestinationOccupied => write!(f, "the destination square is occupied"),
+            MoveError::WrongDirection => write!(f, "uncrowned pieces may only move forward"),
+            MoveError::NothingToCapture => write!(f, "there is no opposing piece to jump"),
+            MoveError::CaptureRequired => write!(f, "a capture is available and must be taken"),
+        }
+    }
+}
+impl std::error::Error for MoveError {}
+
+impl Default for GameEngine {
+    fn default() -> GameEngine {
+        GameEngine::new()
+    }
 }
 
 impl GameEngine {
     pub fn new() -> GameEngine {
         let mut engine = GameEngine {
-            board: [[None; 8]; 8],
+            board: Bitboard::default(),
             current_turn: PieceColor::Black,
             move_count: 0,
+            pending_jump: None,
+            status: GameStatus::InProgress,
+            history: Vec::new(),
+            undone: Vec::new(),
+            positions: Vec::new(),
+            quiet_moves: 0,
+            draw_move_limit: DEFAULT_DRAW_MOVE_LIMIT,
+            generation: 0,
+            hash: 0,
+            observers: Vec::new(),
         };
-        engine.initialize_pieces();
+        engine.reset();
+        engine
+    }
+
+    /// Returns to the starting position and forgets the game played so far.
+    pub fn reset(&mut self) {
+        self.board = Bitboard::default();
+        self.initialize_pieces();
+        self.current_turn = PieceColor::Black;
+        self.move_count = 0;
+        self.pending_jump = None;
+        self.status = GameStatus::InProgress;
+        self.history.clear();
+        self.undone.clear();
+        self.quiet_moves = 0;
+        self.hash = zobrist::hash(&self.board, self.current_turn);
+        self.positions = vec![self.position()];
+        self.generation = self.generation.wrapping_add(1);
+        self.observers.iter_mut().for_each(|o| o.game_reset());
+    }
+
+    /// Swaps in another game, e.g. a loaded position, keeping this engine's
+    /// observers and moving `generation` forward so that hosts notice the change.
+    pub fn replace(&mut self, other: GameEngine) {
+        let generation = self.generation.wrapping_add(1);
+        let observers = std::mem::take(&mut self.observers);
+        *self = other;
+        self.generation = generation;
+        self.observers = observers;
+        self.observers.iter_mut().for_each(|o| o.game_reset());
+    }
+
+    /// Copies the game without its observers, so that moves can be tried out
+    /// on the copy without the host hearing about them.
+    pub fn detached(&self) -> GameEngine {
+        GameEngine {
+            board: self.board,
+            current_turn: self.current_turn,
+            move_count: self.move_count,
+            pending_jump: self.pending_jump,
+            status: self.status,
+            history: self.history.clone(),
+            undone: self.undone.clone(),
+            positions: self.positions.clone(),
+            quiet_moves: self.quiet_moves,
+            draw_move_limit: self.draw_move_limit,
+            generation: self.generation,
+            hash: self.hash,
+            observers: Vec::new(),
+        }
+    }
+
+    pub fn add_observer(&mut self, observer: Box<dyn GameObserver>) {
+        self.observers.push(observer);
+    }
+
+    /// Sets up an arbitrary position, e.g. a puzzle loaded from FEN. Pieces on
+    /// light squares are left out.
+    pub fn from_position(
+        current_turn: PieceColor,
+        pieces: &[(Coordinate, GamePiece)],
+    ) -> GameEngine {
+        let mut engine = GameEngine::new();
+        engine.board = Bitboard::from_pieces(pieces);
+        engine.current_turn = current_turn;
+        engine.hash = zobrist::hash(&engine.board, current_turn);
+        engine.positions = vec![engine.position()];
+        engine.update_status();
         engine
     }
 
@@ -27,82 +192,199 @@ impl GameEngine {
             .iter()
             .zip([0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2].iter())
             .map(|(a, b)| (*a as usize, *b as usize))
-            .for_each(|(x, y)| self.board[x][y] = Some(GamePiece::new(PieceColor::White)));
+            .for_each(|(x, y)| {
+                let piece = GamePiece::new(PieceColor::White);
+                self.board.set(Coordinate(x, y), Some(piece))
+            });
 
         [0, 2, 4, 6, 1, 3, 5, 7, 0, 2, 4, 6]
             .iter()
             .zip([5, 5, 5, 5, 6, 6, 6, 6, 7, 7, 7, 7].iter())
             .map(|(a, b)| (*a as usize, *b as usize))
-            .for_each(|(x, y)| self.board[x][y] = Some(GamePiece::new(PieceColor::Black)))
+            .for_each(|(x, y)| {
+                let piece = GamePiece::new(PieceColor::Black);
+                self.board.set(Coordinate(x, y), Some(piece))
+            })
+    }
+
+    pub fn move_piece(&mut self, mv: &Move) -> Result<MoveResult, MoveError> {
+        if self.status != GameStatus::InProgress {
+            return Err(MoveError::GameOver);
+        }
+        if !self.legal_moves().contains(mv) {
+            return Err(self.rejection_reason(mv));
+        }
+        self.undone.clear();
+        Ok(self.play(mv))
     }
 
-    pub fn move_piece(&mut self, mv: &Move) -> Result<MoveResult, ()> {
-        let legal_moves = self.legal_moves();
-        if !legal_moves.contains(mv) {
-            return Err(());
+    /// Takes back the last move, returning it, or `None` if there is nothing to undo.
+    pub fn undo(&mut self) -> Option<MoveResult> {
+        let entry = self.history.pop()?;
+        let (turn, status) = (self.current_turn, self.status);
+        let Move { from, to } = entry.result.mv;
+        self.board.set(from, Some(entry.piece));
+        self.board.set(to, None);
+        if let Some((at, captured)) = entry.result.captured {
+            self.board.set(at, Some(captured));
+        }
+        self.current_turn = entry.current_turn;
+        self.move_count = entry.move_count;
+        self.pending_jump = entry.pending_jump;
+        self.status = entry.status;
+        self.quiet_moves = entry.quiet_moves;
+        self.hash = entry.hash;
+        self.positions.pop();
+        self.generation = self.generation.wrapping_add(1);
+        self.undone.push(entry.result.mv);
+        for observer in self.observers.iter_mut() {
+            observer.move_undone(&entry.result, entry.piece);
+            if turn != entry.current_turn {
+                observer.turn_changed(entry.current_turn);
+            }
+            if status != entry.status {
+                observer.game_resumed();
+            }
         }
+        Some(entry.result)
+    }
+
+    /// Replays the most recently undone move, or returns `None` if there is none.
+    pub fn redo(&mut self) -> Option<MoveResult> {
+        let mv = self.undone.pop()?;
+        Some(self.play(&mv))
+    }
+
+    // Applies an already validated move and records it in the history.
+    fn play(&mut self, mv: &Move) -> MoveResult {
         let Coordinate(fx, fy) = mv.from;
         let Coordinate(tx, ty) = mv.to;
-        let piece = self.board[fx][fy].unwrap();
+        let piece = self.board.get(mv.from).unwrap();
+        let (current_turn, move_count) = (self.current_turn, self.move_count);
+        let (pending_jump, status) = (self.pending_jump, self.status);
+        let (quiet_moves, hash) = (self.quiet_moves, self.hash);
+        self.generation = self.generation.wrapping_add(1);
         let midpiece_coordinate = self.midpiece_coordinate(fx, fy, tx, ty);
+        let mut captured = None;
         if let Some(Coordinate(x, y)) = midpiece_coordinate {
-            self.board[x][y] = None; // remove the jumped piece
+            captured = self.board.get(Coordinate(x, y)).map(|p| (Coordinate(x, y), p));
+            self.board.set(Coordinate(x, y), None); // remove the jumped piece
+        }
+        if let Some((at, captured)) = captured {
+            self.hash ^= zobrist::piece(at, captured);
         }
         // Move piece from source to dest
-        self.board[tx][ty] = Some(piece);
-        self.board[fx][fy] = None;
+        self.board.set(mv.to, Some(piece));
+        self.board.set(mv.from, None);
+        self.hash ^= zobrist::piece(mv.from, piece) ^ zobrist::piece(mv.to, piece);
+        self.observers.iter_mut().for_each(|o| o.piece_moved(mv));
+        if let Some((at, captured)) = captured {
+            self.observers.iter_mut().for_each(|o| o.piece_captured(at, captured));
+        }
         let crowned = if self.should_crown(piece, mv.to) {
             self.crown_piece(mv.to);
             true
         } else {
             false
         };
-        self.advance_turn();
-        Ok(MoveResult {
-            mv: mv.clone(),
-            crowned: crowned,
-        })
-    }
-
-    fn legal_moves(&self) -> Vec<Move> {
-        let mut moves: Vec<Move> = Vec::new();
-        for col in 0..8 {
-            for row in 0..8 {
-                if let Some(piece) = self.board[col][row] {
-                    if piece.color == self.current_turn {
-                        let loc = Coordinate(col, row);
-                        let mut vmoves = self.valid_moves_from(loc);
-                        moves.append(&mut vmoves);
-                    }
-                }
-            }
+        // After a capture the same piece keeps jumping while it can, unless it was just crowned
+        let turn_continues = mv.is_jump() && !crowned && !self.board.jumps_from(mv.to).is_empty();
+        if turn_continues {
+            self.pending_jump = Some(mv.to);
+        } else {
+            self.pending_jump = None;
+            self.advance_turn();
         }
-        moves
-    }
-
-    fn valid_moves_from(&self, loc: Coordinate) -> Vec<Move> {
-        let Coordinate(x, y) = loc;
-        if let Some(p) = self.board[x][y] {
-            let mut jumps = loc
-                .jump_targets_from()
-                .filter(|t| self.valid_jump(&p, loc, &t))
-                .map(|ref t| Move {
-                    from: loc.clone(),
-                    to: t.clone(),
-                }).collect::<Vec<Move>>();
-
-            let mut moves = loc
-                .move_targets_from()
-                .filter(|t| self.valid_move(&p, loc, &t))
-                .map(|ref t| Move {
-                    from: loc.clone(),
-                    to: t.clone(),
-                }).collect::<Vec<Move>>();
-            jumps.append(&mut moves);
-            jumps
+        // Only moves by kings that capture nothing count toward the move limit
+        if captured.is_some() || !piece.crowned {
+            self.quiet_moves = 0;
         } else {
-            Vec::new()
+            self.quiet_moves += 1;
         }
+        self.positions.push(self.position());
+        self.update_status();
+        let result = MoveResult {
+            mv: *mv,
+            crowned,
+            turn_continues,
+            captured,
+        };
+        self.history.push(HistoryEntry {
+            result,
+            piece,
+            current_turn,
+            move_count,
+            pending_jump,
+            status,
+            quiet_moves,
+            hash,
+        });
+        result
+    }
+
+    // Explains why a move that is not in `legal_moves` was refused.
+    fn rejection_reason(&self, mv: &Move) -> MoveError {
+        for coord in [mv.from, mv.to] {
+            if !coord.on_board() {
+                return MoveError::OffBoard(coord);
+            }
+        }
+        let Coordinate(fx, fy) = mv.from;
+        let Coordinate(tx, ty) = mv.to;
+        let piece = match self.board.get(mv.from) {
+            Some(p) => p,
+            None => return MoveError::NoPieceAtSource,
+        };
+        if piece.color != self.current_turn {
+            return MoveError::WrongTurn;
+        }
+        if self.pending_jump.is_some_and(|loc| loc != mv.from) {
+            return MoveError::MustContinueJump;
+        }
+        let (dx, dy) = (fx.abs_diff(tx), fy.abs_diff(ty));
+        if dx != dy || dx == 0 {
+            return MoveError::NotDiagonal;
+        }
+        if dx > 2 {
+            return MoveError::TooFar;
+        }
+        if self.board.get(mv.to).is_some() {
+            return MoveError::DestinationOccupied;
+        }
+        if !self.valid_direction(&piece, mv.from, &mv.to) {
+            return MoveError::WrongDirection;
+        }
+        if mv.is_jump() {
+            return MoveError::NothingToCapture;
+        }
+        if self.pending_jump.is_some() {
+            return MoveError::MustContinueJump;
+        }
+        MoveError::CaptureRequired
+    }
+
+    /// Moves the side to move may play. Captures are mandatory: if any jump is
+    /// available, only jumps are legal.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        if self.status != GameStatus::InProgress {
+            return Vec::new();
+        }
+        self.moves_on_board()
+    }
+
+    // The moves the position allows, whether or not the game is over.
+    fn moves_on_board(&self) -> Vec<Move> {
+        match self.pending_jump {
+            Some(loc) => self.board.jumps_from(loc),
+            None => self.board.moves(self.current_turn),
+        }
+    }
+
+    pub fn legal_moves_from(&self, loc: Coordinate) -> Vec<Move> {
+        self.legal_moves()
+            .into_iter()
+            .filter(|m| m.from == loc)
+            .collect()
     }
 
     fn midpiece_coordinate(&self, fx: usize, fy: usize, tx: usize, ty: usize) -> Option<Coordinate> {
@@ -121,46 +403,459 @@ impl GameEngine {
     }
 
     fn crown_piece(&mut self, coord: Coordinate) {
-        if let Some(piece) = &mut self.board[coord.0][coord.1] {
-            *piece = GamePiece::crowned(piece.clone());
+        if let Some(piece) = self.board.get(coord) {
+            let king = GamePiece::crowned(piece);
+            self.hash ^= zobrist::piece(coord, piece) ^ zobrist::piece(coord, king);
+            self.board.set(coord, Some(king));
+            self.observers.iter_mut().for_each(|o| o.piece_crowned(coord));
         }
     }
 
     fn advance_turn(&mut self) {
-        self.current_turn = match self.current_turn {
-            PieceColor::White => PieceColor::Black,
-            PieceColor::Black => PieceColor::White,
-        };
+        self.hash ^= zobrist::side(self.current_turn) ^ zobrist::side(self.current_turn.opponent());
+        self.current_turn = self.current_turn.opponent();
         self.move_count += 1;
+        let turn = self.current_turn;
+        self.observers.iter_mut().for_each(|o| o.turn_changed(turn));
     }
 
-    fn valid_move(&self, piece: &GamePiece, from: Coordinate, to: &Coordinate) -> bool {
-        let Coordinate(tx, ty) = *to;
-        if tx < 8 && ty < 8 {
-            self.board[tx][ty].is_none()
+    // The side to move loses when it has no pieces left or none of them can move.
+    fn update_status(&mut self) {
+        let limit_reached = self.draw_move_limit > 0 && self.quiet_moves >= self.draw_move_limit;
+        let status = if self.moves_on_board().is_empty() {
+            GameStatus::Won(self.current_turn.opponent())
+        } else if self.repetitions() >= 3 || limit_reached {
+            GameStatus::Draw
         } else {
-            false
+            GameStatus::InProgress
+        };
+        if status == self.status {
+            return;
+        }
+        self.status = status;
+        for observer in self.observers.iter_mut() {
+            match status {
+                GameStatus::InProgress => observer.game_resumed(),
+                _ => observer.game_over(status),
+            }
         }
     }
 
-    fn valid_jump(&self, piece: &GamePiece, from: Coordinate, to: &Coordinate) -> bool {
-        let Coordinate(fx, fy) = from;
-        let Coordinate(tx, ty) = *to;
-        if (fx as isize - tx as isize).abs() == 2 && (fy as isize - ty as isize).abs() == 2 {
-            if let Some(Coordinate(mx, my)) = self.midpiece_coordinate(fx, fy, tx, ty) {
-                if let Some(mid_piece) = self.board[mx][my] {
-                    return mid_piece.color != piece.color;
-                }
-            }
+    fn position(&self) -> Position {
+        (self.board, self.current_turn, self.pending_jump)
+    }
+
+    // How many times the current position has occurred, including now.
+    fn repetitions(&self) -> usize {
+        let current = self.position();
+        self.positions.iter().filter(|p| **p == current).count()
+    }
+
+    // Men only advance (White toward y=7, Black toward y=0); kings go either way.
+    fn valid_direction(&self, piece: &GamePiece, from: Coordinate, to: &Coordinate) -> bool {
+        if piece.crowned {
+            return true;
+        }
+        match piece.color {
+            PieceColor::White => to.1 > from.1,
+            PieceColor::Black => to.1 < from.1,
         }
-        false
     }
+
     pub fn current_turn(&self) -> PieceColor {
         self.current_turn
     }
 
-    pub fn get_piece(&self, coord: Coordinate) -> Result<Option<GamePiece>, Box<dyn std::error::Error>> {
-        // Your implementation here
-        Ok(None)
+    pub fn status(&self) -> GameStatus {
+        self.status
+    }
+
+    pub fn pieces(&self) -> Vec<(Coordinate, GamePiece)> {
+        self.board.pieces()
+    }
+
+    pub fn bitboard(&self) -> Bitboard {
+        self.board
+    }
+
+    /// Changes every time the board or turn changes, so callers can skip
+    /// redrawing a game they have already drawn.
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Zobrist hash of the pieces on the board and the side to move; equal
+    /// positions always hash the same, whatever moves led to them.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// The piece that has to keep jumping, while a multi-jump is under way.
+    pub fn pending_jump(&self) -> Option<Coordinate> {
+        self.pending_jump
+    }
+
+    pub fn move_count(&self) -> u32 {
+        self.move_count
+    }
+
+    pub fn history(&self) -> Vec<MoveResult> {
+        self.history.iter().map(|entry| entry.result).collect()
+    }
+
+    /// Moves that `redo` would replay, the next one last.
+    pub fn undone_moves(&self) -> Vec<Move> {
+        self.undone.clone()
+    }
+
+    /// The side to move and the pieces before the first move in `history`.
+    pub fn starting_position(&self) -> (PieceColor, Vec<(Coordinate, GamePiece)>) {
+        let (board, turn, _) = &self.positions[0];
+        (*turn, board.pieces())
+    }
+
+    pub fn draw_move_limit(&self) -> u32 {
+        self.draw_move_limit
+    }
+
+    /// Sets how many consecutive moves without a capture or a man moving
+    /// may be played before the game is drawn; 0 turns the rule off. The
+    /// status is checked again against the new limit straight away.
+    pub fn set_draw_move_limit(&mut self, limit: u32) {
+        self.draw_move_limit = limit;
+        self.update_status();
+    }
+
+    pub fn get_piece(&self, coord: Coordinate) -> Result<Option<GamePiece>, BoardError> {
+        if !coord.on_board() {
+            return Err(BoardError::OffBoard(coord));
+        }
+        Ok(self.board.get(coord))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::random;
+
+    const DIAGONALS: [(isize, isize); 4] = [(-1, -1), (1, -1), (-1, 1), (1, 1)];
+
+    fn piece(color: PieceColor, crowned: bool) -> GamePiece {
+        GamePiece { color, crowned }
+    }
+
+    fn offset(Coordinate(x, y): Coordinate, (dx, dy): (isize, isize), n: isize) -> Coordinate {
+        Coordinate(
+            (x as isize + dx * n) as usize,
+            (y as isize + dy * n) as usize,
+        )
+    }
+
+    fn forward(color: PieceColor) -> isize {
+        match color {
+            PieceColor::White => 1,
+            PieceColor::Black => -1,
+        }
+    }
+
+    fn assert_same_moves(mut actual: Vec<Move>, expected: &[Move]) {
+        assert_eq!(
+            actual.len(),
+            expected.len(),
+            "{:?} vs {:?}",
+            actual,
+            expected
+        );
+        actual.retain(|m| !expected.contains(m));
+        assert!(actual.is_empty(), "unexpected moves {:?}", actual);
+    }
+
+    #[test]
+    fn steps_follow_piece_direction() {
+        let from = Coordinate(3, 4);
+        for color in [PieceColor::White, PieceColor::Black] {
+            for crowned in [false, true] {
+                let mut engine = GameEngine::from_position(color, &[(from, piece(color, crowned))]);
+                let (allowed, refused): (Vec<_>, Vec<_>) = DIAGONALS
+                    .iter()
+                    .map(|&d| {
+                        (
+                            d,
+                            Move {
+                                from,
+                                to: offset(from, d, 1),
+                            },
+                        )
+                    })
+                    .partition(|((_, dy), _)| crowned || *dy == forward(color));
+                let allowed: Vec<Move> = allowed.into_iter().map(|(_, m)| m).collect();
+                assert_same_moves(engine.legal_moves(), &allowed);
+                for (_, mv) in refused {
+                    assert_eq!(engine.move_piece(&mv), Err(MoveError::WrongDirection));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn jumps_follow_piece_direction() {
+        let from = Coordinate(3, 4);
+        for color in [PieceColor::White, PieceColor::Black] {
+            for crowned in [false, true] {
+                let mut pieces = vec![(from, piece(color, crowned))];
+                for d in DIAGONALS {
+                    pieces.push((offset(from, d, 1), piece(color.opponent(), false)));
+                }
+                let mut engine = GameEngine::from_position(color, &pieces);
+                let (allowed, refused): (Vec<_>, Vec<_>) = DIAGONALS
+                    .iter()
+                    .map(|&d| {
+                        (
+                            d,
+                            Move {
+                                from,
+                                to: offset(from, d, 2),
+                            },
+                        )
+                    })
+                    .partition(|((_, dy), _)| crowned || *dy == forward(color));
+                let allowed: Vec<Move> = allowed.into_iter().map(|(_, m)| m).collect();
+                assert_same_moves(engine.legal_moves(), &allowed);
+                for (_, mv) in refused {
+                    assert_eq!(engine.move_piece(&mv), Err(MoveError::WrongDirection));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn men_cannot_jump_backwards_even_when_it_is_the_only_capture() {
+        let from = Coordinate(3, 4);
+        for color in [PieceColor::White, PieceColor::Black] {
+            let behind = offset(from, (1, -forward(color)), 1);
+            let pieces = [
+                (from, piece(color, false)),
+                (behind, piece(color.opponent(), false)),
+            ];
+            let engine = GameEngine::from_position(color, &pieces);
+            assert!(engine.legal_moves().iter().all(|m| !m.is_jump()));
+        }
+    }
+
+    #[test]
+    fn get_piece_reports_occupied_and_empty_squares() {
+        let engine = GameEngine::new();
+        let white = Ok(Some(GamePiece::new(PieceColor::White)));
+        let black = Ok(Some(GamePiece::new(PieceColor::Black)));
+        assert_eq!(engine.get_piece(Coordinate(1, 0)), white);
+        assert_eq!(engine.get_piece(Coordinate(0, 7)), black);
+        assert_eq!(engine.get_piece(Coordinate(0, 3)), Ok(None));
+        // Light squares never hold a piece
+        assert_eq!(engine.get_piece(Coordinate(0, 0)), Ok(None));
+    }
+
+    #[test]
+    fn get_piece_rejects_off_board_squares() {
+        let engine = GameEngine::new();
+        let negative = -1i32 as usize;
+        for coord in [
+            Coordinate(8, 0),
+            Coordinate(0, 8),
+            Coordinate(negative, 0),
+            Coordinate(3, negative),
+            Coordinate(usize::MAX, 0),
+            Coordinate(usize::MAX, usize::MAX),
+        ] {
+            assert_eq!(engine.get_piece(coord), Err(BoardError::OffBoard(coord)));
+        }
+    }
+
+    // Counts `game_over` and `game_resumed` calls.
+    struct StatusObserver(std::sync::Arc<std::sync::Mutex<(u32, u32)>>);
+
+    impl GameObserver for StatusObserver {
+        fn game_over(&mut self, _status: GameStatus) {
+            self.0.lock().unwrap().0 += 1;
+        }
+        fn game_resumed(&mut self) {
+            self.0.lock().unwrap().1 += 1;
+        }
+    }
+
+    #[test]
+    fn undoing_the_winning_move_resumes_the_game() {
+        let pieces = [
+            (Coordinate(2, 3), piece(PieceColor::White, false)),
+            (Coordinate(3, 4), piece(PieceColor::Black, false)),
+        ];
+        let mut engine = GameEngine::from_position(PieceColor::White, &pieces);
+        let counts = std::sync::Arc::new(std::sync::Mutex::new((0, 0)));
+        engine.add_observer(Box::new(StatusObserver(counts.clone())));
+
+        let mv = Move::new((2, 3), (4, 5));
+        engine.move_piece(&mv).unwrap();
+        assert_eq!(engine.status(), GameStatus::Won(PieceColor::White));
+        assert_eq!(*counts.lock().unwrap(), (1, 0));
+
+        engine.undo();
+        assert_eq!(engine.status(), GameStatus::InProgress);
+        assert_eq!(*counts.lock().unwrap(), (1, 1));
+
+        engine.redo();
+        assert_eq!(engine.status(), GameStatus::Won(PieceColor::White));
+        assert_eq!(*counts.lock().unwrap(), (2, 1));
+    }
+
+    // Kings far apart that can shuffle without ever meeting.
+    fn kings_only() -> GameEngine {
+        let pieces = [
+            (Coordinate(1, 2), piece(PieceColor::White, true)),
+            (Coordinate(6, 5), piece(PieceColor::Black, true)),
+        ];
+        GameEngine::from_position(PieceColor::Black, &pieces)
+    }
+
+    // Black and White each step a king out and back, returning to the start.
+    const SHUFFLE: [((usize, usize), (usize, usize)); 4] = [
+        ((6, 5), (5, 6)),
+        ((1, 2), (2, 3)),
+        ((5, 6), (6, 5)),
+        ((2, 3), (1, 2)),
+    ];
+
+    #[test]
+    fn threefold_repetition_is_a_draw() {
+        let mut engine = kings_only();
+        for (i, (from, to)) in SHUFFLE.iter().cycle().take(8).enumerate() {
+            assert_eq!(engine.status(), GameStatus::InProgress, "before move {}", i);
+            engine.move_piece(&Move::new(*from, *to)).unwrap();
+        }
+        // The starting position has now occurred three times
+        assert_eq!(engine.status(), GameStatus::Draw);
+        assert_eq!(
+            engine.move_piece(&Move::new((6, 5), (5, 6))),
+            Err(MoveError::GameOver)
+        );
+    }
+
+    #[test]
+    fn quiet_move_limit_is_a_draw() {
+        let mut engine = kings_only();
+        engine.set_draw_move_limit(3);
+        for (from, to) in &SHUFFLE[..2] {
+            engine.move_piece(&Move::new(*from, *to)).unwrap();
+        }
+        assert_eq!(engine.status(), GameStatus::InProgress);
+        engine.move_piece(&Move::new((5, 6), (6, 5))).unwrap();
+        assert_eq!(engine.status(), GameStatus::Draw);
+    }
+
+    #[test]
+    fn moving_a_man_restarts_the_quiet_move_count() {
+        let pieces = [
+            (Coordinate(1, 2), piece(PieceColor::White, true)),
+            (Coordinate(6, 5), piece(PieceColor::Black, true)),
+            (Coordinate(0, 7), piece(PieceColor::Black, false)),
+        ];
+        let mut engine = GameEngine::from_position(PieceColor::Black, &pieces);
+        engine.set_draw_move_limit(2);
+        engine.move_piece(&Move::new((6, 5), (5, 6))).unwrap();
+        engine.move_piece(&Move::new((1, 2), (2, 3))).unwrap();
+        assert_eq!(engine.status(), GameStatus::Draw);
+
+        let mut engine = GameEngine::from_position(PieceColor::Black, &pieces);
+        engine.set_draw_move_limit(2);
+        engine.move_piece(&Move::new((0, 7), (1, 6))).unwrap();
+        engine.move_piece(&Move::new((1, 2), (2, 3))).unwrap();
+        assert_eq!(engine.status(), GameStatus::InProgress);
+    }
+
+    #[test]
+    fn zero_draw_move_limit_disables_the_rule() {
+        let mut engine = kings_only();
+        engine.set_draw_move_limit(0);
+        for (from, to) in SHUFFLE.iter().chain(&SHUFFLE[..3]) {
+            engine.move_piece(&Move::new(*from, *to)).unwrap();
+            assert_eq!(engine.status(), GameStatus::InProgress);
+        }
+    }
+
+    #[test]
+    fn changing_the_draw_move_limit_updates_the_status() {
+        let mut engine = kings_only();
+        for (from, to) in &SHUFFLE[..3] {
+            engine.move_piece(&Move::new(*from, *to)).unwrap();
+        }
+        let counts = std::sync::Arc::new(std::sync::Mutex::new((0, 0)));
+        engine.add_observer(Box::new(StatusObserver(counts.clone())));
+        engine.set_draw_move_limit(3);
+        assert_eq!(engine.status(), GameStatus::Draw);
+        engine.set_draw_move_limit(DEFAULT_DRAW_MOVE_LIMIT);
+        assert_eq!(engine.status(), GameStatus::InProgress);
+        assert_eq!(*counts.lock().unwrap(), (1, 1));
+    }
+
+    fn assert_hash_matches_board(engine: &GameEngine) {
+        assert_eq!(
+            engine.hash(),
+            zobrist::hash(&engine.bitboard(), engine.current_turn())
+        );
+    }
+
+    #[test]
+    fn incremental_hash_matches_a_full_rehash_in_random_games() {
+        let mut seed = 0x3f0a_5c2e_91b7_d846;
+        let mut crownings = 0;
+        for _ in 0..100 {
+            let mut engine = GameEngine::new();
+            assert_hash_matches_board(&engine);
+            while engine.status() == GameStatus::InProgress {
+                match random(&mut seed) % 10 {
+                    0 => {
+                        engine.undo();
+                    }
+                    1 => {
+                        engine.redo();
+                    }
+                    _ => {
+                        let moves = engine.legal_moves();
+                        let mv = moves[random(&mut seed) % moves.len()];
+                        if engine.move_piece(&mv).unwrap().crowned {
+                            crownings += 1;
+                        }
+                    }
+                }
+                assert_hash_matches_board(&engine);
+            }
+            while engine.undo().is_some() {
+                assert_hash_matches_board(&engine);
+            }
+            assert_eq!(engine.hash(), GameEngine::new().hash());
+        }
+        assert!(crownings > 0);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip_keeps_history_and_undone_moves() {
+        let mut engine = GameEngine::new();
+        for _ in 0..4 {
+            let mv = engine.legal_moves()[0];
+            engine.move_piece(&mv).unwrap();
+        }
+        let undone = engine.undo().unwrap();
+
+        let json = serde_json::to_string(&engine).unwrap();
+        let mut loaded: GameEngine = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.pieces(), engine.pieces());
+        assert_eq!(loaded.history(), engine.history());
+        assert_eq!(loaded.hash(), engine.hash());
+        assert_eq!(loaded.current_turn(), engine.current_turn());
+
+        assert_eq!(loaded.redo(), Some(undone));
+        assert_eq!(engine.redo(), Some(undone));
+        assert_eq!(loaded.pieces(), engine.pieces());
+        assert_eq!(loaded.hash(), engine.hash());
     }
 }
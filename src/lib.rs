@@ -1,21 +1,161 @@
-mod board;
-mod game;
+//! Checkers engine exported to WebAssembly.
+//!
+//! `move_piece` returns 1 when the move ends the turn and 2 when the same piece
+//! must keep jumping. A rejected move returns one of these negative codes:
+//!
+//! | code | reason |
+//! |------|--------|
+//! | -1   | the game is already over |
+//! | -2   | a coordinate is off the board |
+//! | -3   | there is no piece on the source square |
+//! | -4   | the piece belongs to the player not on turn |
+//! | -5   | another piece is in the middle of a multi-jump |
+//! | -6   | the move is not diagonal |
+//! | -7   | the move is more than two squares |
+//! | -8   | the destination square is occupied |
+//! | -9   | an uncrowned piece tried to move backwards |
+//! | -10  | a jump does not pass over an opposing piece |
+//! | -11  | a capture is available and must be taken |
+//!
+//! Events reach the host through `notify_*` functions that the default build
+//! imports from the `env` module, every argument an i32:
+//!
+//! | import | arguments | called when |
+//! |--------|-----------|-------------|
+//! | `notify_piecemoved` | from x, from y, to x, to y | a piece moves, also back by an undo |
+//! | `notify_piececrowned` | x, y | a piece is crowned |
+//! | `notify_pieceremoved` | x, y | a piece is captured |
+//! | `notify_pieceplaced` | x, y, piece flags | an undo puts a piece back |
+//! | `notify_gameover` | status as returned by `get_game_status` | the game ends |
+//! | `notify_gamereset` | none | the board is replaced by a new or loaded game |
+//! | `notify_gameresumed` | none | an undo takes back the move that ended the game |
+//!
+//! They fire while the export that caused them still holds the game, so a
+//! callback must not call back into the module (e.g. `get_board` to redraw
+//! after `notify_gamereset`); it should note what changed and act once that
+//! export has returned.
+//!
+//! Building with the `event-queue` feature drops those imports and queues the
+//! events instead; `poll_event` takes the oldest one, writing five little-endian
+//! i32 values (the game handle, 0 for the default game, followed by up to four
+//! arguments) and returning its kind, or 0 when the queue is empty:
+//!
+//! | kind | event | arguments |
+//! |------|-------|-----------|
+//! | 1    | piece moved | from x, from y, to x, to y |
+//! | 2    | piece crowned | x, y |
+//! | 3    | piece captured | x, y |
+//! | 4    | piece placed back by an undo | x, y, piece flags |
+//! | 5    | turn changed (queue only) | color flag of the side to move |
+//! | 6    | game over | status as returned by `get_game_status` |
+//! | 7    | game reset | none |
+//! | 8    | game back in progress, e.g. after undoing the move that ended it | none |
+//!
+//! Several games can run side by side: `create_game` returns a handle that the
+//! `game_*` exports take as their first argument. Those exports return -12 if
+//! the handle does not name a live game. Host callbacks fired while handling a
+//! `game_*` call belong to the game named by that call's handle.
+//!
+//! With the `wasm-bindgen` feature, [`bindings::Game`] offers the same engine to
+//! JavaScript as a class returning plain objects, alongside the raw exports.
+//!
+//! Native embedders can use [`game::GameEngine`] directly and subscribe to its
+//! changes with a [`observer::GameObserver`].
+
+pub mod ai;
+#[cfg(feature = "wasm-bindgen")]
+pub mod bindings;
+pub mod bitboard;
+pub mod board;
+mod events;
+pub mod fen;
+pub mod game;
+pub mod observer;
+pub mod pdn;
+pub mod save;
+mod slots;
+#[cfg(test)]
+mod testing;
+pub mod transposition;
+mod zobrist;
 
 #[macro_use]
 extern crate lazy_static;
 
 use board::{Coordinate, GamePiece, Move, PieceColor};
-use game::GameEngine;
+use events::HostObserver;
+use game::{GameEngine, GameStatus, MoveError};
 
 use mut_static::MutStatic;
+use slots::SlotMap;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use transposition::TranspositionTable;
 lazy_static! {
-    pub static ref GAME_ENGINE: MutStatic<GameEngine> = { MutStatic::from(GameEngine::new()) };
+    pub static ref GAME_ENGINE: MutStatic<GameEngine> = MutStatic::from(host_engine(0));
+    static ref GAMES: MutStatic<SlotMap<GameEngine>> = MutStatic::from(SlotMap::new());
+    // Searches kept between `continue_thinking` calls, by game handle
+    static ref SEARCHES: MutStatic<HashMap<i32, ai::Search>> = MutStatic::from(HashMap::new());
+}
+
+const ERR_INVALID_HANDLE: i32 = -12;
+
+// Bytes of transposition table given to each game's search
+static SEARCH_MEMORY: AtomicUsize = AtomicUsize::new(ai::DEFAULT_TABLE_BYTES);
+
+/// Starts a new game and returns its handle, or 0 if no more games fit.
+#[unsafe(no_mangle)]
+pub extern "C" fn create_game() -> i32 {
+    let mut games = GAMES.write().unwrap();
+    let Some(handle) = games.insert(GameEngine::new()) else {
+        return 0;
+    };
+    if let Some(engine) = games.get_mut(handle) {
+        engine.add_observer(Box::new(HostObserver::new(handle)));
+    }
+    handle
+}
+
+// A new game whose changes are reported to the host.
+fn host_engine(handle: i32) -> GameEngine {
+    let mut engine = GameEngine::new();
+    engine.add_observer(Box::new(HostObserver::new(handle)));
+    engine
+}
+
+/// Frees a game created by `create_game`. Returns 1, or -12 for an invalid handle.
+#[unsafe(no_mangle)]
+pub extern "C" fn destroy_game(handle: i32) -> i32 {
+    let mut games = GAMES.write().unwrap();
+    match games.remove(handle) {
+        Some(_) => {
+            SEARCHES.write().unwrap().remove(&handle);
+            1
+        }
+        None => ERR_INVALID_HANDLE,
+    }
+}
+
+fn with_game<T: From<i32>>(handle: i32, f: impl FnOnce(&mut GameEngine) -> T) -> T {
+    let mut games = GAMES.write().unwrap();
+    match games.get_mut(handle) {
+        Some(engine) => f(engine),
+        None => ERR_INVALID_HANDLE.into(),
+    }
 }
 
 #[unsafe(no_mangle)]
 pub extern "C" fn get_pieces(x: i32, y: i32) -> i32 {
-    let engine = GAME_ENGINE.read().unwrap();
+    piece_at(&GAME_ENGINE.read().unwrap(), x, y)
+}
 
+#[unsafe(no_mangle)]
+pub extern "C" fn game_get_pieces(handle: i32, x: i32, y: i32) -> i32 {
+    with_game(handle, |engine| piece_at(engine, x, y))
+}
+
+fn piece_at(engine: &GameEngine, x: i32, y: i32) -> i32 {
+    // Negative coordinates wrap to huge values and are rejected as off-board
     let piece = engine.get_piece(Coordinate(x as usize, y as usize));
     match piece {
         Ok(Some(p)) => p.into(),
@@ -26,51 +166,618 @@ pub extern "C" fn get_pieces(x: i32, y: i32) -> i32 {
 
 #[unsafe(no_mangle)]
 pub extern "C" fn get_current_turn() -> i32 {
-    let engine = GAME_ENGINE.read().unwrap();
+    GamePiece::new(GAME_ENGINE.read().unwrap().current_turn()).into()
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn game_get_current_turn(handle: i32) -> i32 {
+    with_game(handle, |engine| GamePiece::new(engine.current_turn()).into())
+}
+
+/// Writes all 64 squares into the buffer at `ptr`, one byte per square at index
+/// `y * 8 + x`, using the `PIECEFLAG` bits (0 for an empty square). Returns the
+/// board generation, as `get_board_generation` would.
+///
+/// # Safety
+///
+/// `ptr` must point to 64 writable bytes of linear memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn get_board(ptr: *mut u8) -> i32 {
+    unsafe { write_board(&GAME_ENGINE.read().unwrap(), ptr) }
+}
+
+/// # Safety
+///
+/// `ptr` must point to 64 writable bytes of linear memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn game_get_board(handle: i32, ptr: *mut u8) -> i32 {
+    with_game(handle, |engine| unsafe { write_board(engine, ptr) })
+}
+
+unsafe fn write_board(engine: &GameEngine, ptr: *mut u8) -> i32 {
+    let buf = unsafe { std::slice::from_raw_parts_mut(ptr, 64) };
+    for (i, square) in buf.iter_mut().enumerate() {
+        *square = match engine.get_piece(Coordinate(i % 8, i / 8)) {
+            Ok(Some(p)) => i32::from(p) as u8,
+            _ => 0,
+        };
+    }
+    engine.generation() as i32
+}
+
+/// Returns a counter that changes whenever the board or turn changes, so the
+/// host can skip redrawing when it matches the last value it saw.
+#[unsafe(no_mangle)]
+pub extern "C" fn get_board_generation() -> i32 {
+    GAME_ENGINE.read().unwrap().generation() as i32
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn game_get_board_generation(handle: i32) -> i32 {
+    with_game(handle, |engine| engine.generation() as i32)
+}
+
+/// Returns 0 while the game is in progress, the winner's color flag
+/// (`PIECEFLAG_BLACK` or `PIECEFLAG_WHITE`) once it is won, and 3 for a draw.
+#[unsafe(no_mangle)]
+pub extern "C" fn get_game_status() -> i32 {
+    status_code(GAME_ENGINE.read().unwrap().status())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn game_get_game_status(handle: i32) -> i32 {
+    with_game(handle, |engine| status_code(engine.status()))
+}
+
+/// Writes the 64-bit Zobrist hash of the current position (pieces and side to
+/// move) as 8 little-endian bytes, for spotting repeated positions or checking
+/// that two copies of a game agree. Returns 1; `game_get_position_hash`
+/// returns the usual error codes instead, since any 64-bit value may be a hash.
+///
+/// # Safety
+///
+/// `ptr` must point to 8 writable bytes of linear memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn get_position_hash(ptr: *mut u8) -> i32 {
+    unsafe { write_hash(&GAME_ENGINE.read().unwrap(), ptr) }
+}
+
+/// # Safety
+///
+/// `ptr` must point to 8 writable bytes of linear memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn game_get_position_hash(handle: i32, ptr: *mut u8) -> i32 {
+    with_game(handle, |engine| unsafe { write_hash(engine, ptr) })
+}
 
-    GamePiece::new(engine.current_turn()).into()
+unsafe fn write_hash(engine: &GameEngine, ptr: *mut u8) -> i32 {
+    let buf = unsafe { std::slice::from_raw_parts_mut(ptr, 8) };
+    buf.copy_from_slice(&engine.hash().to_le_bytes());
+    1
+}
+
+const STATUS_IN_PROGRESS: i32 = 0;
+const STATUS_DRAW: i32 = 3;
+fn status_code(status: GameStatus) -> i32 {
+    match status {
+        GameStatus::InProgress => STATUS_IN_PROGRESS,
+        GameStatus::Won(PieceColor::Black) => PIECEFLAG_BLACK as i32,
+        GameStatus::Won(PieceColor::White) => PIECEFLAG_WHITE as i32,
+        GameStatus::Draw => STATUS_DRAW,
+    }
 }
 
 const PIECEFLAG_BLACK: u8 = 1;
 const PIECEFLAG_WHITE: u8 = 2;
 const PIECEFLAG_CROWN: u8 = 4;
-impl Into<i32> for GamePiece {
-    fn into(self) -> i32 {
+impl From<GamePiece> for i32 {
+    fn from(piece: GamePiece) -> i32 {
         let mut val: u8 = 0;
-        if self.color == PieceColor::Black {
+        if piece.color == PieceColor::Black {
             val += PIECEFLAG_BLACK;
-        } else if self.color == PieceColor::White {
+        } else if piece.color == PieceColor::White {
             val += PIECEFLAG_WHITE;
         }
-        if self.crowned {
+        if piece.crowned {
             val += PIECEFLAG_CROWN;
         }
         val as i32
     }
 }
 
+/// Returns 1 if the move ended the turn, 2 if the same piece must keep jumping,
+/// or a negative error code (see the crate docs) if the move was rejected.
 #[unsafe(no_mangle)]
 pub extern "C" fn move_piece(fx: i32, fy: i32, tx: i32, ty: i32) -> i32 {
-    let mut engine = GAME_ENGINE.write().unwrap();
+    play_move(&mut GAME_ENGINE.write().unwrap(), fx, fy, tx, ty)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn game_move_piece(handle: i32, fx: i32, fy: i32, tx: i32, ty: i32) -> i32 {
+    with_game(handle, |engine| play_move(engine, fx, fy, tx, ty))
+}
+
+fn play_move(engine: &mut GameEngine, fx: i32, fy: i32, tx: i32, ty: i32) -> i32 {
     let mv = Move::new((fx as usize, fy as usize), (tx as usize, ty as usize));
     let res = engine.move_piece(&mv);
     match res {
-        Ok(mr) => {
-            unsafe {
-                notify_piecemoved(fx, fy, tx, ty);
-            }
-            if mr.crowned {
-                unsafe {
-                    notify_piececrowned(tx, ty);
-                }
-            }
+        Ok(mr) if mr.turn_continues => 2,
+        Ok(_) => 1,
+        Err(e) => error_code(e),
+    }
+}
+
+/// Writes the legal moves into the `len`-byte buffer at `ptr` as 5-byte records
+/// (from x, from y, to x, to y, 1 if the move is a capture else 0) and returns
+/// how many moves are legal. Only as many records as fit are written, so a
+/// buffer shorter than one record, even with a null `ptr`, just counts them.
+///
+/// # Safety
+///
+/// `ptr` must point to `len` writable bytes of linear memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn get_legal_moves(ptr: *mut u8, len: usize) -> i32 {
+    let moves = GAME_ENGINE.read().unwrap().legal_moves();
+    unsafe { write_moves(&moves, ptr, len) }
+}
+
+/// # Safety
+///
+/// `ptr` must point to `len` writable bytes of linear memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn game_get_legal_moves(handle: i32, ptr: *mut u8, len: usize) -> i32 {
+    with_game(handle, |engine| unsafe { write_moves(&engine.legal_moves(), ptr, len) })
+}
+
+/// Like `get_legal_moves`, but only for the piece at (`x`, `y`).
+///
+/// # Safety
+///
+/// `ptr` must point to `len` writable bytes of linear memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn get_legal_moves_from(x: i32, y: i32, ptr: *mut u8, len: usize) -> i32 {
+    let engine = GAME_ENGINE.read().unwrap();
+    let moves = engine.legal_moves_from(Coordinate(x as usize, y as usize));
+    unsafe { write_moves(&moves, ptr, len) }
+}
+
+/// # Safety
+///
+/// `ptr` must point to `len` writable bytes of linear memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn game_get_legal_moves_from(
+    handle: i32,
+    x: i32,
+    y: i32,
+    ptr: *mut u8,
+    len: usize,
+) -> i32 {
+    with_game(handle, |engine| {
+        let moves = engine.legal_moves_from(Coordinate(x as usize, y as usize));
+        unsafe { write_moves(&moves, ptr, len) }
+    })
+}
+
+const MOVE_RECORD_LEN: usize = 5;
+unsafe fn write_moves(moves: &[Move], ptr: *mut u8, len: usize) -> i32 {
+    if len < MOVE_RECORD_LEN {
+        return moves.len() as i32;
+    }
+    let buf = unsafe { std::slice::from_raw_parts_mut(ptr, len) };
+    for (mv, record) in moves.iter().zip(buf.chunks_exact_mut(MOVE_RECORD_LEN)) {
+        let Move { from, to } = *mv;
+        record.copy_from_slice(&[
+            from.0 as u8,
+            from.1 as u8,
+            to.0 as u8,
+            to.1 as u8,
+            mv.is_jump() as u8,
+        ]);
+    }
+    moves.len() as i32
+}
+
+/// Starts the game over from the initial position and fires `notify_gamereset`
+/// so that the host redraws once this call has returned.
+#[unsafe(no_mangle)]
+pub extern "C" fn new_game() -> i32 {
+    reset(&mut GAME_ENGINE.write().unwrap())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn game_new_game(handle: i32) -> i32 {
+    with_game(handle, reset)
+}
+
+fn reset(engine: &mut GameEngine) -> i32 {
+    engine.reset();
+    1
+}
+
+/// Takes back the last move. Returns 1 if a move was undone, 0 if there was none.
+#[unsafe(no_mangle)]
+pub extern "C" fn undo_move() -> i32 {
+    undo(&mut GAME_ENGINE.write().unwrap())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn game_undo_move(handle: i32) -> i32 {
+    with_game(handle, undo)
+}
+
+fn undo(engine: &mut GameEngine) -> i32 {
+    match engine.undo() {
+        Some(_) => 1,
+        None => 0,
+    }
+}
+
+/// Replays the last undone move. Returns 1 if a move was redone, 0 if there was none.
+#[unsafe(no_mangle)]
+pub extern "C" fn redo_move() -> i32 {
+    redo(&mut GAME_ENGINE.write().unwrap())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn game_redo_move(handle: i32) -> i32 {
+    with_game(handle, redo)
+}
+
+fn redo(engine: &mut GameEngine) -> i32 {
+    match engine.redo() {
+        Some(_) => 1,
+        None => 0,
+    }
+}
+
+/// Lets the computer play the side to move, searching `depth` turns ahead. The
+/// whole turn is played, including every jump of a multi-jump, firing the usual
+/// callbacks. Returns the number of moves played, 0 if the game is over. The
+/// search uses the game's transposition table (see `set_search_memory`), so it
+/// drops any search left by `think`.
+#[unsafe(no_mangle)]
+pub extern "C" fn computer_move(depth: i32) -> i32 {
+    play_computer_move(0, &mut GAME_ENGINE.write().unwrap(), depth)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn game_computer_move(handle: i32, depth: i32) -> i32 {
+    with_game(handle, |engine| play_computer_move(handle, engine, depth))
+}
+
+fn play_computer_move(handle: i32, engine: &mut GameEngine, depth: i32) -> i32 {
+    let mut searches = SEARCHES.write().unwrap();
+    let mut table = match searches.remove(&handle) {
+        Some(search) => search.into_table(),
+        None => TranspositionTable::new(SEARCH_MEMORY.load(Ordering::Relaxed)),
+    };
+    let mut played = 0;
+    while let Some(mv) = ai::best_move(engine, depth.max(1) as u32, &mut table) {
+        played += 1;
+        if !engine.move_piece(&mv).unwrap().turn_continues {
+            break;
+        }
+    }
+    // Keep the table for the next search of this game
+    searches.insert(handle, ai::Search::with_table(engine, table));
+    played
+}
+
+/// Searches the current position for up to `budget` nodes, deepening one turn
+/// at a time, and returns the best move found without playing it. The move is
+/// packed as `fx << 12 | fy << 8 | tx << 4 | ty`, with bit 16 set once more
+/// searching cannot change it; -1 means the game is over.
+#[unsafe(no_mangle)]
+pub extern "C" fn think(budget: i32) -> i32 {
+    search(0, &GAME_ENGINE.read().unwrap(), budget, false)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn game_think(handle: i32, budget: i32) -> i32 {
+    with_game(handle, |engine| search(handle, engine, budget, false))
+}
+
+/// Like `think`, but carries on from the search left by the previous `think` or
+/// `continue_thinking` call as long as the position has not changed since, so a
+/// host can spread a deep search over several short calls.
+#[unsafe(no_mangle)]
+pub extern "C" fn continue_thinking(budget: i32) -> i32 {
+    search(0, &GAME_ENGINE.read().unwrap(), budget, true)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn game_continue_thinking(handle: i32, budget: i32) -> i32 {
+    with_game(handle, |engine| search(handle, engine, budget, true))
+}
+
+fn search(handle: i32, engine: &GameEngine, budget: i32, resume: bool) -> i32 {
+    let mut searches = SEARCHES.write().unwrap();
+    let search = match searches.remove(&handle) {
+        Some(search) if resume && search.generation() == engine.generation() => search,
+        Some(search) => ai::Search::with_table(engine, search.into_table()),
+        None => {
+            let table = TranspositionTable::new(SEARCH_MEMORY.load(Ordering::Relaxed));
+            ai::Search::with_table(engine, table)
+        }
+    };
+    let search = searches.entry(handle).or_insert(search);
+    let finished = search.run(budget.max(0) as u64);
+    match search.best_move() {
+        Some(Move { from, to }) => {
+            let packed = (from.0 << 12 | from.1 << 8 | to.0 << 4 | to.1) as i32;
+            if finished { packed | 1 << 16 } else { packed }
+        }
+        None => -1,
+    }
+}
+
+/// Sets how many bytes of linear memory each game's search may use for its
+/// transposition table (1 MiB by default). Searches under way are dropped, and
+/// the next `think`, `continue_thinking` or `computer_move` call allocates a
+/// table of the new size.
+#[unsafe(no_mangle)]
+pub extern "C" fn set_search_memory(bytes: i32) {
+    SEARCH_MEMORY.store(bytes.max(0) as usize, Ordering::Relaxed);
+    SEARCHES.write().unwrap().clear();
+}
+
+/// Writes transposition table statistics for the search left by the last
+/// `think` or `continue_thinking` call as four little-endian u32 values: probes,
+/// hits, stores, and stores that evicted another position. Returns 1, or 0 if
+/// there has been no search.
+///
+/// # Safety
+///
+/// `ptr` must point to 16 writable bytes of linear memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn get_search_stats(ptr: *mut u8) -> i32 {
+    unsafe { write_search_stats(0, ptr) }
+}
+
+/// # Safety
+///
+/// `ptr` must point to 16 writable bytes of linear memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn game_get_search_stats(handle: i32, ptr: *mut u8) -> i32 {
+    with_game(handle, |_| unsafe { write_search_stats(handle, ptr) })
+}
+
+unsafe fn write_search_stats(handle: i32, ptr: *mut u8) -> i32 {
+    let searches = SEARCHES.read().unwrap();
+    let Some(search) = searches.get(&handle) else {
+        return 0;
+    };
+    let stats = search.table().stats();
+    let buf = unsafe { std::slice::from_raw_parts_mut(ptr, 16) };
+    let values = [stats.probes, stats.hits, stats.stores, stats.overwrites];
+    for (value, bytes) in values.iter().zip(buf.chunks_exact_mut(4)) {
+        let value = (*value).min(u32::MAX as u64) as u32;
+        bytes.copy_from_slice(&value.to_le_bytes());
+    }
+    1
+}
+
+fn error_code(err: MoveError) -> i32 {
+    match err {
+        MoveError::GameOver => -1,
+        MoveError::OffBoard(_) => -2,
+        MoveError::NoPieceAtSource => -3,
+        MoveError::WrongTurn => -4,
+        MoveError::MustContinueJump => -5,
+        MoveError::NotDiagonal => -6,
+        MoveError::TooFar => -7,
+        MoveError::DestinationOccupied => -8,
+        MoveError::WrongDirection => -9,
+        MoveError::NothingToCapture => -10,
+        MoveError::CaptureRequired => -11,
+    }
+}
+
+/// Saves the game into the `len`-byte buffer at `ptr` in the compact binary
+/// format of the `save` module and returns the number of bytes written. If the
+/// buffer is too small nothing is written and the size needed is returned negated.
+/// A game too long for the format, with 65536 or more played or undone moves, is
+/// not saved and 0 is returned.
+///
+/// # Safety
+///
+/// `ptr` must point to `len` writable bytes of linear memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn save_state(ptr: *mut u8, len: usize) -> i32 {
+    unsafe { write_state(&GAME_ENGINE.read().unwrap(), ptr, len) }
+}
+
+/// # Safety
+///
+/// `ptr` must point to `len` writable bytes of linear memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn game_save_state(handle: i32, ptr: *mut u8, len: usize) -> i32 {
+    with_game(handle, |engine| unsafe { write_state(engine, ptr, len) })
+}
+
+unsafe fn write_state(engine: &GameEngine, ptr: *mut u8, len: usize) -> i32 {
+    let Some(data) = save::encode(engine) else {
+        return 0;
+    };
+    if data.len() > len {
+        return -(data.len() as i32);
+    }
+    let buf = unsafe { std::slice::from_raw_parts_mut(ptr, len) };
+    buf[..data.len()].copy_from_slice(&data);
+    data.len() as i32
+}
+
+/// Replaces the game with one written by `save_state`. Returns 1 on success;
+/// otherwise the current game is kept and the result is -1 if the data is not a
+/// save, -2 if it was written by an unsupported version, -3 if its checksum does
+/// not match and -4 if its contents are inconsistent. A successful load fires
+/// `notify_gamereset`; redraw once this call has returned.
+///
+/// # Safety
+///
+/// `ptr` must point to `len` readable bytes of linear memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn load_state(ptr: *const u8, len: usize) -> i32 {
+    let data = unsafe { std::slice::from_raw_parts(ptr, len) };
+    restore(&mut GAME_ENGINE.write().unwrap(), save::decode(data))
+}
+
+/// # Safety
+///
+/// `ptr` must point to `len` readable bytes of linear memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn game_load_state(handle: i32, ptr: *const u8, len: usize) -> i32 {
+    let data = unsafe { std::slice::from_raw_parts(ptr, len) };
+    let state = save::decode(data);
+    with_game(handle, |engine| restore(engine, state))
+}
+
+fn restore(engine: &mut GameEngine, state: Result<GameEngine, save::SaveError>) -> i32 {
+    match state {
+        Ok(state) => {
+            engine.replace(state);
             1
         }
-        Err(_) => 0,
+        Err(save::SaveError::NotASave) => -1,
+        Err(save::SaveError::UnsupportedVersion(_)) => -2,
+        Err(save::SaveError::ChecksumMismatch) => -3,
+        Err(save::SaveError::Corrupt) => -4,
     }
 }
 
-unsafe extern "C" {
-    fn notify_piecemoved(fromX: i32, fromY: i32, toX: i32, toY: i32);
-    fn notify_piececrowned(x: i32, y: i32);
+/// Reserves `len` bytes of linear memory for the host to write into, e.g. the
+/// string passed to `load_position`. Release it again with `dealloc`.
+#[unsafe(no_mangle)]
+pub extern "C" fn alloc(len: usize) -> *mut u8 {
+    let mut buf = Vec::<u8>::with_capacity(len);
+    let ptr = buf.as_mut_ptr();
+    std::mem::forget(buf);
+    ptr
+}
+
+/// # Safety
+///
+/// `ptr` and `len` must come from a single earlier call to `alloc`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn dealloc(ptr: *mut u8, len: usize) {
+    unsafe {
+        drop(Vec::from_raw_parts(ptr, 0, len));
+    }
+}
+
+/// Replaces the current game with the FEN position (e.g. `W:W21,22,K30:B1,2`)
+/// stored as UTF-8 at `ptr`. Returns 1 on success and 0 if the text is not a
+/// valid position, leaving the current game untouched. A successful load fires
+/// `notify_gamereset`; redraw once this call has returned.
+///
+/// # Safety
+///
+/// `ptr` must point to `len` readable bytes of linear memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn load_position(ptr: *const u8, len: usize) -> i32 {
+    let position = unsafe { parse_position(ptr, len) };
+    load(&mut GAME_ENGINE.write().unwrap(), position)
+}
+
+/// # Safety
+///
+/// `ptr` must point to `len` readable bytes of linear memory.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn game_load_position(handle: i32, ptr: *const u8, len: usize) -> i32 {
+    let position = unsafe { parse_position(ptr, len) };
+    with_game(handle, |engine| load(engine, position))
+}
+
+unsafe fn parse_position(ptr: *const u8, len: usize) -> Option<GameEngine> {
+    let bytes = unsafe { std::slice::from_raw_parts(ptr, len) };
+    std::str::from_utf8(bytes).ok().and_then(|s| fen::parse(s).ok())
+}
+
+fn load(engine: &mut GameEngine, position: Option<GameEngine>) -> i32 {
+    match position {
+        Some(position) => {
+            engine.replace(position);
+            1
+        }
+        None => 0,
+    }
+}
+
+/// Takes the oldest queued event, writing its game handle and arguments as five
+/// i32 values at `ptr`. Returns the event kind, or 0 if no event is waiting.
+///
+/// # Safety
+///
+/// `ptr` must point to 20 writable bytes of linear memory.
+#[cfg(feature = "event-queue")]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn poll_event(ptr: *mut u8) -> i32 {
+    let Some((handle, event)) = events::poll() else {
+        return 0;
+    };
+    let (kind, args) = event.encode();
+    let buf = unsafe { std::slice::from_raw_parts_mut(ptr, 20) };
+    for (value, bytes) in [handle].iter().chain(&args).zip(buf.chunks_exact_mut(4)) {
+        bytes.copy_from_slice(&value.to_le_bytes());
+    }
+    kind
+}
+
+#[cfg(all(test, feature = "event-queue"))]
+mod tests {
+    use super::*;
+
+    fn drain(handle: i32) -> Vec<(i32, [i32; 4])> {
+        let mut events = Vec::new();
+        let mut buf = [0u8; 20];
+        loop {
+            let kind = unsafe { poll_event(buf.as_mut_ptr()) };
+            if kind == 0 {
+                return events;
+            }
+            let values: Vec<i32> = buf
+                .chunks_exact(4)
+                .map(|b| i32::from_le_bytes(b.try_into().unwrap()))
+                .collect();
+            if values[0] == handle {
+                events.push((kind, [values[1], values[2], values[3], values[4]]));
+            }
+        }
+    }
+
+    #[test]
+    fn moves_queue_events_for_their_game() {
+        let handle = create_game();
+        assert_eq!(game_move_piece(handle, 0, 5, 1, 4), 1);
+        let white: i32 = GamePiece::new(PieceColor::White).into();
+        assert_eq!(
+            drain(handle),
+            vec![(1, [0, 5, 1, 4]), (5, [white, 0, 0, 0])]
+        );
+        assert_eq!(drain(handle), vec![]);
+        assert_eq!(destroy_game(handle), 1);
+    }
+
+    #[test]
+    fn continue_thinking_resumes_an_unchanged_position() {
+        let handle = create_game();
+        assert_eq!(game_think(handle, 2000) & 1 << 16, 0);
+        game_continue_thinking(handle, 2000);
+        assert_eq!(SEARCHES.read().unwrap()[&handle].nodes(), 4000);
+
+        assert_eq!(game_move_piece(handle, 0, 5, 1, 4), 1);
+        game_continue_thinking(handle, 2000);
+        assert_eq!(SEARCHES.read().unwrap()[&handle].nodes(), 2000);
+        assert_eq!(destroy_game(handle), 1);
+    }
+
+    #[test]
+    fn computer_moves_reuse_the_search_memory() {
+        let handle = create_game();
+        assert_eq!(game_computer_move(handle, 2), 1);
+        let bytes = SEARCHES.read().unwrap()[&handle].table().size_bytes();
+        assert_eq!(bytes, SEARCH_MEMORY.load(Ordering::Relaxed));
+        assert_eq!(game_computer_move(handle, 2), 1);
+        assert_eq!(destroy_game(handle), 1);
+    }
 }
@@ -0,0 +1,21 @@
+use super::board::{Coordinate, GamePiece, Move, PieceColor};
+use super::game::{GameStatus, MoveResult};
+
+/// Receives the changes a `GameEngine` makes, in the order it makes them.
+/// Every method does nothing by default, so observers only implement the
+/// events they care about.
+pub trait GameObserver: Send + Sync {
+    fn piece_moved(&mut self, _mv: &Move) {}
+    fn piece_captured(&mut self, _at: Coordinate, _piece: GamePiece) {}
+    fn piece_crowned(&mut self, _at: Coordinate) {}
+    fn turn_changed(&mut self, _turn: PieceColor) {}
+    fn game_over(&mut self, _status: GameStatus) {}
+    /// `piece` is the moved piece as it stood before the move, i.e. uncrowned
+    /// if the move crowned it.
+    fn move_undone(&mut self, _result: &MoveResult, _piece: GamePiece) {}
+    /// The game is in progress again after being over, e.g. because an undo
+    /// took back the move that ended it.
+    fn game_resumed(&mut self) {}
+    /// The whole board changed, e.g. after `reset` or loading a position.
+    fn game_reset(&mut self) {}
+}
@@ -0,0 +1,301 @@
+use std::fmt;
+
+use super::board::{Coordinate, Move, PieceColor};
+use super::fen::{self, FenError};
+use super::game::{GameEngine, GameStatus, MoveError};
+
+// Black moves first, so it is the first player in PDN result strings.
+const RESULTS: [&str; 7] = ["1-0", "0-1", "1/2-1/2", "2-0", "0-2", "1-1", "*"];
+const LINE_WIDTH: usize = 80;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PdnError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: PdnErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PdnErrorKind {
+    UnexpectedToken(String),
+    Unterminated(char),
+    InvalidSquare(u32),
+    InvalidFen(FenError),
+    IllegalMove(MoveError),
+}
+
+impl fmt::Display for PdnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+        match &self.kind {
+            PdnErrorKind::UnexpectedToken(t) => write!(f, "unexpected token `{}`", t),
+            PdnErrorKind::Unterminated(c) => write!(f, "missing closing `{}`", c),
+            PdnErrorKind::InvalidSquare(n) => write!(f, "{} is not a square number", n),
+            PdnErrorKind::InvalidFen(e) => write!(f, "invalid FEN tag: {}", e),
+            PdnErrorKind::IllegalMove(e) => write!(f, "illegal move: {}", e),
+        }
+    }
+}
+impl std::error::Error for PdnError {}
+
+/// Writes the game played so far as PDN, with a `Result` tag and move numbers.
+/// Games that did not start from the initial layout also get `SetUp` and
+/// `FEN` tags describing where they began.
+pub fn export(engine: &GameEngine) -> String {
+    let result = match engine.status() {
+        GameStatus::InProgress => "*",
+        GameStatus::Won(PieceColor::Black) => "1-0",
+        GameStatus::Won(PieceColor::White) => "0-1",
+        GameStatus::Draw => "1/2-1/2",
+    };
+
+    // Consecutive jumps by one piece form a single turn, e.g. `9x18x27`
+    let mut turns: Vec<String> = Vec::new();
+    let mut continuing = false;
+    for mr in engine.history() {
+        let to = square(mr.mv.to);
+        if continuing {
+            let turn = turns.last_mut().unwrap();
+            turn.push_str(&format!("x{}", to));
+        } else {
+            let sep = if mr.mv.is_jump() { 'x' } else { '-' };
+            turns.push(format!("{}{}{}", square(mr.mv.from), sep, to));
+        }
+        continuing = mr.turn_continues;
+    }
+
+    let (start_turn, start_pieces) = engine.starting_position();
+    let mut tokens: Vec<String> = Vec::new();
+    let mut rest = &turns[..];
+    let mut first = 1;
+    if start_turn == PieceColor::White && !turns.is_empty() {
+        // White's opening move takes the place of Black's reply in turn 1
+        tokens.push(format!("1... {}", turns[0]));
+        rest = &turns[1..];
+        first = 2;
+    }
+    for (i, pair) in rest.chunks(2).enumerate() {
+        // Keep each move number on the same line as Black's move
+        tokens.push(format!("{}. {}", i + first, pair[0]));
+        tokens.extend(pair[1..].iter().cloned());
+    }
+    tokens.push(result.to_string());
+
+    let mut out = format!("[Result \"{}\"]\n", result);
+    if (start_turn, &start_pieces) != (PieceColor::Black, &GameEngine::new().pieces()) {
+        let start = GameEngine::from_position(start_turn, &start_pieces);
+        out.push_str("[SetUp \"1\"]\n");
+        out.push_str(&format!("[FEN \"{}\"]\n", fen::export(&start)));
+    }
+    out.push('\n');
+    let mut line_len = 0;
+    for token in tokens {
+        if line_len > 0 && line_len + 1 + token.len() > LINE_WIDTH {
+            out.push('\n');
+            line_len = 0;
+        } else if line_len > 0 {
+            out.push(' ');
+            line_len += 1;
+        }
+        out.push_str(&token);
+        line_len += token.len();
+    }
+    out.push('\n');
+    out
+}
+
+fn square(coord: Coordinate) -> u32 {
+    coord.to_square().expect("pieces only stand on dark squares")
+}
+
+/// Replays a PDN game through `GameEngine::move_piece`, starting from the
+/// position in its `FEN` tag if it has one and the initial layout otherwise.
+pub fn replay(pdn: &str) -> Result<GameEngine, PdnError> {
+    let (tags, tokens) = tokenize(pdn)?;
+    let mut engine = GameEngine::new();
+    for tag in &tags {
+        if let Some(fen) = tag_value(tag.text, "FEN") {
+            engine = fen::parse(fen).map_err(|e| tag.error(PdnErrorKind::InvalidFen(e)))?;
+        }
+    }
+    for token in tokens {
+        let word = match token.text.rfind('.') {
+            // Move numbers may be attached to the move, as in `1.11-15`
+            Some(i) if token.text[..i].chars().all(|c| c.is_ascii_digit() || c == '.') => {
+                &token.text[i + 1..]
+            }
+            _ => token.text,
+        };
+        if word.is_empty() {
+            continue;
+        }
+        if RESULTS.contains(&word) {
+            break;
+        }
+        for mv in parse_move(word).map_err(|kind| token.error(kind))? {
+            engine
+                .move_piece(&mv)
+                .map_err(|e| token.error(PdnErrorKind::IllegalMove(e)))?;
+        }
+    }
+    Ok(engine)
+}
+
+// The quoted value of a `Name "value"` tag pair, if it is the named tag.
+fn tag_value<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let (tag_name, value) = tag.trim().split_once(char::is_whitespace)?;
+    if tag_name != name {
+        return None;
+    }
+    value.trim().strip_prefix('"')?.strip_suffix('"')
+}
+
+fn parse_move(word: &str) -> Result<Vec<Move>, PdnErrorKind> {
+    let unexpected = || PdnErrorKind::UnexpectedToken(word.to_string());
+    let mut squares = Vec::new();
+    for part in word.split(['-', 'x']) {
+        let n: u32 = part.parse().map_err(|_| unexpected())?;
+        squares.push(Coordinate::from_square(n).ok_or(PdnErrorKind::InvalidSquare(n))?);
+    }
+    if squares.len() < 2 {
+        return Err(unexpected());
+    }
+    Ok(squares
+        .windows(2)
+        .map(|w| Move { from: w[0], to: w[1] })
+        .collect())
+}
+
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+    column: usize,
+}
+
+impl Token<'_> {
+    fn error(&self, kind: PdnErrorKind) -> PdnError {
+        PdnError {
+            line: self.line,
+            column: self.column,
+            kind,
+        }
+    }
+}
+
+type Tokens<'a> = (Vec<Token<'a>>, Vec<Token<'a>>);
+
+// Splits PDN into the contents of its `[tags]` and the words of its
+// movetext, skipping `{comments}`.
+fn tokenize(pdn: &str) -> Result<Tokens<'_>, PdnError> {
+    let mut tags = Vec::new();
+    let mut tokens = Vec::new();
+    let mut chars = pdn.char_indices().peekable();
+    let (mut line, mut column) = (1, 1);
+    while let Some((start, c)) = chars.next() {
+        let (start_line, start_column) = (line, column);
+        advance(c, &mut line, &mut column);
+        let closing = match c {
+            '[' => Some(']'),
+            '{' => Some('}'),
+            _ => None,
+        };
+        if let Some(closing) = closing {
+            loop {
+                match chars.next() {
+                    Some((i, c)) => {
+                        advance(c, &mut line, &mut column);
+                        if c == closing {
+                            if closing == ']' {
+                                tags.push(Token {
+                                    text: &pdn[start + 1..i],
+                                    line: start_line,
+                                    column: start_column,
+                                });
+                            }
+                            break;
+                        }
+                    }
+                    None => {
+                        return Err(PdnError {
+                            line: start_line,
+                            column: start_column,
+                            kind: PdnErrorKind::Unterminated(closing),
+                        });
+                    }
+                }
+            }
+        } else if !c.is_whitespace() {
+            let mut end = start + c.len_utf8();
+            while let Some(&(i, c)) = chars.peek() {
+                if c.is_whitespace() || c == '[' || c == '{' {
+                    break;
+                }
+                advance(c, &mut line, &mut column);
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            tokens.push(Token {
+                text: &pdn[start..end],
+                line: start_line,
+                column: start_column,
+            });
+        }
+    }
+    Ok((tags, tokens))
+}
+
+fn advance(c: char, line: &mut usize, column: &mut usize) {
+    if c == '\n' {
+        *line += 1;
+        *column = 1;
+    } else {
+        *column += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn games_from_the_initial_layout_have_no_fen_tag() {
+        let mut engine = GameEngine::new();
+        let mv = engine.legal_moves()[0];
+        engine.move_piece(&mv).unwrap();
+        let pdn = export(&engine);
+        assert!(!pdn.contains("[FEN"));
+        assert_eq!(replay(&pdn).unwrap().pieces(), engine.pieces());
+    }
+
+    #[test]
+    fn games_from_a_fen_position_replay_from_it() {
+        let mut engine = fen::parse("B:W18,K30:B14,22").unwrap();
+        let mv = engine.legal_moves()[0];
+        engine.move_piece(&mv).unwrap();
+        let pdn = export(&engine);
+        assert!(pdn.contains("[SetUp \"1\"]"));
+        assert!(pdn.contains("[FEN \"B:W18,K30:B14,22\"]"));
+
+        let replayed = replay(&pdn).unwrap();
+        assert_eq!(replayed.pieces(), engine.pieces());
+        assert_eq!(replayed.current_turn(), engine.current_turn());
+        assert_eq!(export(&replayed), pdn);
+    }
+
+    #[test]
+    fn white_to_move_starts_with_an_ellipsis() {
+        let mut engine = fen::parse("W:W18,K30:B14,22").unwrap();
+        let mv = engine.legal_moves()[0];
+        engine.move_piece(&mv).unwrap();
+        let pdn = export(&engine);
+        assert!(pdn.contains("\n1... "));
+        assert_eq!(replay(&pdn).unwrap().pieces(), engine.pieces());
+    }
+
+    #[test]
+    fn invalid_fen_tags_are_reported() {
+        let err = replay("[FEN \"X:W18:B14\"]\n1. 14-17 *").err().unwrap();
+        assert_eq!((err.line, err.column), (1, 1));
+        assert!(matches!(err.kind, PdnErrorKind::InvalidFen(_)));
+    }
+}
@@ -0,0 +1,206 @@
+use std::fmt;
+
+use super::board::{Coordinate, GamePiece, Move, PieceColor};
+use super::game::GameEngine;
+
+// Layout, all integers little-endian:
+//
+//   magic "CKRS", version
+//   starting board (16 bytes), starting side to move, draw move limit (u32)
+//   played move count (u16), undone move count (u16), then 2 bytes per move
+//     (from square, to square), played moves first and undone moves in redo order
+//   current board (16 bytes), current side to move, move count (u32)
+//   CRC-32 of everything above (u32)
+//
+// Boards pack the 32 dark squares two to a byte, 4 bits each using the
+// `PIECEFLAG` bits. The game is restored by replaying its moves from the
+// starting position, so the move list is checked for legality and the
+// stored current position has to match the replayed one.
+const MAGIC: &[u8; 4] = b"CKRS";
+const VERSION: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SaveError {
+    NotASave,
+    UnsupportedVersion(u8),
+    ChecksumMismatch,
+    Corrupt,
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::NotASave => write!(f, "data is not a saved game"),
+            SaveError::UnsupportedVersion(v) => write!(f, "unsupported save version {}", v),
+            SaveError::ChecksumMismatch => write!(f, "save checksum does not match"),
+            SaveError::Corrupt => write!(f, "saved game is inconsistent"),
+        }
+    }
+}
+impl std::error::Error for SaveError {}
+
+/// Writes the game in the layout above, or returns `None` if it has more played
+/// or undone moves than the 16-bit counts can hold.
+pub fn encode(engine: &GameEngine) -> Option<Vec<u8>> {
+    let (turn, pieces) = engine.starting_position();
+    let played: Vec<Move> = engine.history().iter().map(|mr| mr.mv).collect();
+    let undone: Vec<Move> = engine.undone_moves().into_iter().rev().collect();
+    let played_len = u16::try_from(played.len()).ok()?;
+    let undone_len = u16::try_from(undone.len()).ok()?;
+
+    let mut out = MAGIC.to_vec();
+    out.push(VERSION);
+    out.extend(pack_board(&pieces));
+    out.push(turn_byte(turn));
+    out.extend(engine.draw_move_limit().to_le_bytes());
+    out.extend(played_len.to_le_bytes());
+    out.extend(undone_len.to_le_bytes());
+    for mv in played.iter().chain(&undone) {
+        out.push(mv.from.to_square().unwrap() as u8);
+        out.push(mv.to.to_square().unwrap() as u8);
+    }
+    out.extend(pack_board(&engine.pieces()));
+    out.push(turn_byte(engine.current_turn()));
+    out.extend(engine.move_count().to_le_bytes());
+    out.extend(crc32(&out).to_le_bytes());
+    Some(out)
+}
+
+pub fn decode(data: &[u8]) -> Result<GameEngine, SaveError> {
+    // Magic, version and checksum are the least any save contains
+    if data.len() < 9 || &data[..4] != MAGIC {
+        return Err(SaveError::NotASave);
+    }
+    if data[4] != VERSION {
+        return Err(SaveError::UnsupportedVersion(data[4]));
+    }
+    let (body, checksum) = data.split_at(data.len() - 4);
+    if crc32(body) != u32::from_le_bytes(checksum.try_into().unwrap()) {
+        return Err(SaveError::ChecksumMismatch);
+    }
+
+    let mut reader = Reader { data: body, pos: 5 };
+    let pieces = unpack_board(reader.take(16)?)?;
+    let turn = turn_from_byte(reader.byte()?)?;
+    let draw_move_limit = reader.u32()?;
+    let played = reader.u16()? as usize;
+    let undone = reader.u16()? as usize;
+    let mut moves = Vec::with_capacity(played + undone);
+    for _ in 0..played + undone {
+        let from = Coordinate::from_square(reader.byte()? as u32).ok_or(SaveError::Corrupt)?;
+        let to = Coordinate::from_square(reader.byte()? as u32).ok_or(SaveError::Corrupt)?;
+        moves.push(Move { from, to });
+    }
+    let current = unpack_board(reader.take(16)?)?;
+    let current_turn = turn_from_byte(reader.byte()?)?;
+    let move_count = reader.u32()?;
+    if reader.pos != body.len() {
+        return Err(SaveError::Corrupt);
+    }
+
+    let mut engine = GameEngine::from_position(turn, &pieces);
+    engine.set_draw_move_limit(draw_move_limit);
+    for mv in &moves {
+        engine.move_piece(mv).map_err(|_| SaveError::Corrupt)?;
+    }
+    for _ in 0..undone {
+        engine.undo();
+    }
+    let mut replayed = engine.pieces();
+    let mut expected = current;
+    replayed.sort_by_key(|(c, _)| c.to_square());
+    expected.sort_by_key(|(c, _)| c.to_square());
+    if replayed != expected
+        || engine.current_turn() != current_turn
+        || engine.move_count() != move_count
+    {
+        return Err(SaveError::Corrupt);
+    }
+    Ok(engine)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SaveError> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or(SaveError::Corrupt)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, SaveError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, SaveError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, SaveError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+fn pack_board(pieces: &[(Coordinate, GamePiece)]) -> [u8; 16] {
+    let mut packed = [0u8; 16];
+    for (coord, piece) in pieces {
+        let index = coord.to_square().unwrap() as usize - 1;
+        let nibble = i32::from(*piece) as u8;
+        packed[index / 2] |= nibble << (4 * (index % 2));
+    }
+    packed
+}
+
+fn unpack_board(packed: &[u8]) -> Result<Vec<(Coordinate, GamePiece)>, SaveError> {
+    let mut pieces = Vec::new();
+    for index in 0..32 {
+        let nibble = (packed[index / 2] >> (4 * (index % 2))) & 0x0f;
+        let color = match nibble & 0b011 {
+            0 if nibble == 0 => continue,
+            1 => PieceColor::Black,
+            2 => PieceColor::White,
+            _ => return Err(SaveError::Corrupt),
+        };
+        if nibble & !0b111 != 0 {
+            return Err(SaveError::Corrupt);
+        }
+        let coord = Coordinate::from_square(index as u32 + 1).unwrap();
+        let crowned = nibble & 0b100 != 0;
+        pieces.push((coord, GamePiece { color, crowned }));
+    }
+    Ok(pieces)
+}
+
+fn turn_byte(color: PieceColor) -> u8 {
+    i32::from(GamePiece::new(color)) as u8
+}
+
+fn turn_from_byte(byte: u8) -> Result<PieceColor, SaveError> {
+    match byte {
+        1 => Ok(PieceColor::Black),
+        2 => Ok(PieceColor::White),
+        _ => Err(SaveError::Corrupt),
+    }
+}
+
+// CRC-32 (IEEE), computed bitwise to avoid a lookup table in linear memory.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
@@ -0,0 +1,65 @@
+// Generational slot map handing out `i32` handles that fit the WASM ABI.
+// A handle packs a 16-bit slot index with the slot's generation, so a
+// handle kept after its slot was freed and reused no longer resolves.
+pub struct SlotMap<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<usize>,
+}
+
+struct Slot<T> {
+    generation: i32,
+    value: Option<T>,
+}
+
+const INDEX_BITS: i32 = 16;
+const MAX_SLOTS: usize = 1 << INDEX_BITS;
+const MAX_GENERATION: i32 = 0x7fff;
+
+impl<T> SlotMap<T> {
+    pub fn new() -> SlotMap<T> {
+        SlotMap {
+            slots: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    // Returns `None` once every slot is in use.
+    pub fn insert(&mut self, value: T) -> Option<i32> {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None if self.slots.len() < MAX_SLOTS => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    value: None,
+                });
+                self.slots.len() - 1
+            }
+            None => return None,
+        };
+        let slot = &mut self.slots[index];
+        // Generations start at 1 so that every handle is positive and non-zero
+        slot.generation = slot.generation % MAX_GENERATION + 1;
+        slot.value = Some(value);
+        Some(slot.generation << INDEX_BITS | index as i32)
+    }
+
+    pub fn remove(&mut self, handle: i32) -> Option<T> {
+        let index = self.index(handle)?;
+        self.free.push(index);
+        self.slots[index].value.take()
+    }
+
+    pub fn get_mut(&mut self, handle: i32) -> Option<&mut T> {
+        let index = self.index(handle)?;
+        self.slots[index].value.as_mut()
+    }
+
+    fn index(&self, handle: i32) -> Option<usize> {
+        let index = (handle & (MAX_SLOTS as i32 - 1)) as usize;
+        let slot = self.slots.get(index)?;
+        if handle <= 0 || slot.value.is_none() || slot.generation != handle >> INDEX_BITS {
+            return None;
+        }
+        Some(index)
+    }
+}
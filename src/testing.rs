@@ -0,0 +1,9 @@
+// Helpers shared by the unit tests.
+
+// xorshift64, so that random games repeat from run to run. `seed` must not be 0.
+pub fn random(seed: &mut u64) -> usize {
+    *seed ^= *seed << 13;
+    *seed ^= *seed >> 7;
+    *seed ^= *seed << 17;
+    *seed as usize
+}
@@ -0,0 +1,156 @@
+// Fixed-size transposition table for the search. Each position hash maps to a
+// single slot; a slot keeps its entry until a search at least as deep, or any
+// search started later, stores another position there.
+use super::board::{Coordinate, Move};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum Bound {
+    // The stored score is the position's value
+    Exact,
+    // The position is worth at least the stored score
+    Lower,
+    // The position is worth at most the stored score
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Entry {
+    pub bound: Bound,
+    pub depth: u32,
+    pub score: i32,
+    pub best: Option<Move>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TableStats {
+    pub probes: u64,
+    pub hits: u64,
+    pub stores: u64,
+    // Stores that evicted a different position
+    pub overwrites: u64,
+}
+
+impl TableStats {
+    pub fn hit_rate(&self) -> f64 {
+        if self.probes == 0 {
+            0.0
+        } else {
+            self.hits as f64 / self.probes as f64
+        }
+    }
+}
+
+// 16 bytes. The slot index already fixes the low bits of the hash, so only
+// the high 32 are kept to tell positions sharing a slot apart.
+#[derive(Clone, Copy, Default)]
+struct Slot {
+    check: u32,
+    score: i32,
+    // From and to square numbers, 0 when there is no move
+    best: [u8; 2],
+    depth: u8,
+    bound: u8,
+    // Search the entry was stored by, 0 for an empty slot
+    age: u8,
+}
+
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+    age: u8,
+    stats: TableStats,
+}
+
+impl TranspositionTable {
+    /// A table taking up about `bytes` bytes; with fewer than 16 it stores nothing.
+    pub fn new(bytes: usize) -> TranspositionTable {
+        TranspositionTable {
+            slots: vec![Slot::default(); bytes / size_of::<Slot>()],
+            age: 1,
+            stats: TableStats::default(),
+        }
+    }
+
+    pub fn size_bytes(&self) -> usize {
+        self.slots.len() * size_of::<Slot>()
+    }
+
+    /// Marks the entries stored so far as belonging to an earlier search, so
+    /// that new entries replace them first, and starts the statistics over.
+    pub fn new_search(&mut self) {
+        self.age = if self.age == u8::MAX { 1 } else { self.age + 1 };
+        self.stats = TableStats::default();
+    }
+
+    pub fn stats(&self) -> TableStats {
+        self.stats
+    }
+
+    pub fn probe(&mut self, hash: u64) -> Option<Entry> {
+        let slot = *self.slot(hash)?;
+        self.stats.probes += 1;
+        if slot.age == 0 || slot.check != (hash >> 32) as u32 {
+            return None;
+        }
+        self.stats.hits += 1;
+        let bound = match slot.bound {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+        let best = match (square(slot.best[0]), square(slot.best[1])) {
+            (Some(from), Some(to)) => Some(Move { from, to }),
+            _ => None,
+        };
+        Some(Entry {
+            bound,
+            depth: slot.depth as u32,
+            score: slot.score,
+            best,
+        })
+    }
+
+    pub fn store(&mut self, hash: u64, entry: Entry) {
+        let age = self.age;
+        let Some(slot) = self.slot(hash) else {
+            return;
+        };
+        let check = (hash >> 32) as u32;
+        let depth = entry.depth.min(u8::MAX as u32) as u8;
+        if slot.age == age && slot.check != check && slot.depth > depth {
+            return;
+        }
+        let overwrite = slot.age != 0 && slot.check != check;
+        let best = entry
+            .best
+            .map_or([0, 0], |mv| [number(mv.from), number(mv.to)]);
+        *slot = Slot {
+            check,
+            score: entry.score,
+            best,
+            depth,
+            bound: entry.bound as u8,
+            age,
+        };
+        self.stats.stores += 1;
+        if overwrite {
+            self.stats.overwrites += 1;
+        }
+    }
+
+    fn slot(&mut self, hash: u64) -> Option<&mut Slot> {
+        if self.slots.is_empty() {
+            return None;
+        }
+        let index = (hash % self.slots.len() as u64) as usize;
+        Some(&mut self.slots[index])
+    }
+}
+
+fn number(coord: Coordinate) -> u8 {
+    coord.to_square().unwrap_or(0) as u8
+}
+
+fn square(number: u8) -> Option<Coordinate> {
+    Coordinate::from_square(number as u32)
+}
@@ -0,0 +1,56 @@
+// Zobrist keys: a position hashes to the XOR of one key per piece on the board,
+// picked by square, color and crown, plus `BLACK_TO_MOVE` when Black is on turn.
+// Keys come from a fixed generator so hashes are the same in every build.
+use super::bitboard::Bitboard;
+use super::board::{Coordinate, GamePiece, PieceColor};
+
+const SEED: u64 = 0x2545_f491_4f6c_dd1d;
+
+// One key for each square (`x * 8 + y`) and kind of piece.
+const PIECE_KEYS: [[u64; 4]; 64] = {
+    let mut keys = [[0; 4]; 64];
+    let mut i = 0;
+    while i < 64 * 4 {
+        keys[i / 4][i % 4] = splitmix64(SEED.wrapping_add(i as u64));
+        i += 1;
+    }
+    keys
+};
+const BLACK_TO_MOVE: u64 = splitmix64(SEED.wrapping_add(64 * 4));
+
+const fn splitmix64(n: u64) -> u64 {
+    let mut z = n.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+pub fn piece(Coordinate(x, y): Coordinate, piece: GamePiece) -> u64 {
+    let kind = match piece.color {
+        PieceColor::White => 0,
+        PieceColor::Black => 1,
+    } + if piece.crowned { 2 } else { 0 };
+    PIECE_KEYS[x * 8 + y][kind]
+}
+
+// Toggled whenever the side to move changes.
+pub fn side(color: PieceColor) -> u64 {
+    match color {
+        PieceColor::White => 0,
+        PieceColor::Black => BLACK_TO_MOVE,
+    }
+}
+
+// Set apart from the piece keys so that a position in the middle of a
+// multi-jump does not hash like the same board with a free choice of moves.
+pub fn pending_jump(Coordinate(x, y): Coordinate) -> u64 {
+    splitmix64(SEED.wrapping_add(64 * 4 + 1 + (x * 8 + y) as u64))
+}
+
+// Hashes a whole board from scratch.
+pub fn hash(board: &Bitboard, turn: PieceColor) -> u64 {
+    board
+        .pieces()
+        .into_iter()
+        .fold(side(turn), |hash, (at, p)| hash ^ piece(at, p))
+}